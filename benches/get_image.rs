@@ -0,0 +1,43 @@
+use std::{
+    hint::black_box,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use kennel_club::{ImageFormat, Kennel};
+use rand::{SeedableRng, rngs::SmallRng};
+
+static RNG_SEED: u64 = 1;
+static ITERATIONS: u32 = 20;
+static CANVAS_SIZES: [u32; 3] = [256, 1024, 4096];
+
+fn time<F: FnMut()>(mut f: F) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
+
+/**
+ * Times `Kennel::get_image` at a few canvas sizes.
+ * The first render at a size is reported separately since it fills the scaled sprite cache.
+ */
+fn main() {
+    let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+    let kennel = Kennel::load(&PathBuf::from("./data"), &mut rng)
+        .expect("Error during kennel initialization");
+
+    for size in CANVAS_SIZES {
+        let render = || {
+            black_box(
+                kennel
+                    .get_image(size, size, ImageFormat::Png)
+                    .expect("Error during image processing"),
+            );
+        };
+
+        let cold = time(render);
+        let warm = time(|| (0..ITERATIONS).for_each(|_| render())) / ITERATIONS;
+
+        println!("get_image {size:>4}px: cold {cold:>10.2?}, warm {warm:>10.2?}/iter");
+    }
+}
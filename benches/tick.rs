@@ -0,0 +1,87 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use kennel_club::{Kennel, creature::Creature};
+use rand::{SeedableRng, rngs::SmallRng};
+
+static RNG_SEED: u64 = 1;
+static TICKS: usize = 10_000;
+static CREATURES: usize = 20;
+
+/**
+ * The system allocator, counting every allocation it hands out.
+ */
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/**
+ * A kennel of copies of the first creature in `./data`, small enough that they all fit.
+ */
+fn load_kennel(rng: &mut SmallRng) -> Kennel {
+    let template = Kennel::load(&PathBuf::from("./data"), rng)
+        .expect("Error during kennel initialization")
+        .creatures()[0]
+        .clone();
+
+    let creatures: Vec<Creature> = (0..CREATURES)
+        .map(|idx| Creature {
+            id: format!("{}-{}", template.id, idx),
+            radius: 0.03,
+            ..template.clone()
+        })
+        .collect();
+    Kennel::new(creatures, rng).expect("Error during kennel initialization")
+}
+
+fn measure<F: FnMut()>(name: &str, mut f: F) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    (0..TICKS).for_each(|_| f());
+    let elapsed = start.elapsed() / TICKS as u32;
+    let allocations = (ALLOCATIONS.load(Ordering::Relaxed) - allocations) as f64 / TICKS as f64;
+
+    println!("{name:>4}: {elapsed:>10.2?}/tick, {allocations:>7.2} allocations/tick");
+}
+
+/**
+ * Compares `Kennel::next` against `Kennel::tick` over the same run.
+ */
+fn main() {
+    let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+    let mut kennel = load_kennel(&mut rng);
+    measure("next", || {
+        (kennel, _) = black_box(kennel.next(&mut rng).expect("Error during tick"));
+    });
+
+    let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+    let mut kennel = load_kennel(&mut rng);
+    measure("tick", || {
+        kennel.tick(&mut rng).expect("Error during tick");
+        black_box(&kennel);
+    });
+}
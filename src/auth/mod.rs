@@ -0,0 +1,3 @@
+pub use owners::Owners;
+
+mod owners;
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+
+/**
+ * Which token lets someone command which creature.
+ * This is handed out to members privately and kept out of the data directory,
+ * since everything in there is public.
+ */
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(try_from = "HashMap<String, String>")]
+pub struct Owners {
+    tokens: HashMap<String, String>,
+}
+
+/**
+ * Compares in the same time wherever the first difference is,
+ * so tokens can't be guessed a byte at a time.
+ */
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+impl TryFrom<HashMap<String, String>> for Owners {
+    type Error = String;
+
+    fn try_from(tokens: HashMap<String, String>) -> Result<Self, String> {
+        let mut owners = Owners::default();
+        for (id, token) in tokens.iter() {
+            owners.set_token(id, token)?;
+        }
+        Ok(owners)
+    }
+}
+
+impl Owners {
+    /**
+     * Loads a json object of creature ids to tokens.
+     */
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read {}. {}", path.display(), e))?;
+        serde_json::from_str(&json).map_err(|e| format!("Unable to deserialize owners. {}", e))
+    }
+
+    /**
+     * Lets whoever has `token` command the creature, which takes a token that isn't empty,
+     * or everyone would have it.
+     */
+    pub fn set_token(&mut self, id: &str, token: &str) -> Result<(), String> {
+        if token.is_empty() {
+            return Err(format!("Creature {} has an empty token", id));
+        }
+        self.tokens.insert(id.to_string(), token.to_string());
+        Ok(())
+    }
+
+    pub fn is_owner(&self, token: &str, id: &str) -> bool {
+        !token.is_empty()
+            && self.tokens.get(id).is_some_and(|owner_token| {
+                constant_time_eq(owner_token.as_bytes(), token.as_bytes())
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_owner() {
+        let owners: Owners = serde_json::from_str(r#"{ "alt": "hunter2" }"#).unwrap();
+        assert!(owners.is_owner("hunter2", "alt"));
+        assert!(!owners.is_owner("hunter3", "alt"));
+        assert!(!owners.is_owner("hunter", "alt"));
+        assert!(!owners.is_owner("hunter2", "someone else"));
+    }
+
+    #[test]
+    fn test_empty_token() {
+        assert!(serde_json::from_str::<Owners>(r#"{ "alt": "" }"#).is_err());
+
+        let mut owners = Owners::default();
+        assert!(owners.set_token("alt", "").is_err());
+        assert!(!owners.is_owner("", "alt"));
+    }
+}
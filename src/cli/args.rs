@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/**
+ * The `--name value` options given to a subcommand.
+ * Options are taken out as they're read, so anything left over was never asked for.
+ */
+#[derive(Debug, Default)]
+pub struct Options {
+    values: HashMap<String, String>,
+}
+
+impl Options {
+    pub fn parse<I: Iterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut values = HashMap::new();
+        let mut args = args.peekable();
+        while let Some(arg) = args.next() {
+            let name = arg.strip_prefix("--").ok_or(format!(
+                "Unexpected argument `{}`, options look like `--name value`",
+                arg
+            ))?;
+
+            let (name, value) = match name.split_once('=') {
+                Some((name, value)) => (name.to_string(), value.to_string()),
+                None => {
+                    let value = args
+                        .next_if(|value| !value.starts_with("--"))
+                        .ok_or(format!("Option `--{}` needs a value", name))?;
+                    (name.to_string(), value)
+                }
+            };
+
+            if values.insert(name.clone(), value).is_some() {
+                return Err(format!("Option `--{}` was given more than once", name));
+            }
+        }
+
+        Ok(Options { values })
+    }
+
+    pub fn take_string(&mut self, name: &str) -> Option<String> {
+        self.values.remove(name)
+    }
+
+    pub fn take<T: FromStr>(&mut self, name: &str) -> Result<Option<T>, String> {
+        self.values
+            .remove(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| format!("Option `--{}` can't be `{}`", name, value))
+            })
+            .transpose()
+    }
+
+    pub fn require<T: FromStr>(&mut self, name: &str) -> Result<T, String> {
+        self.take(name)?
+            .ok_or(format!("Option `--{}` is required", name))
+    }
+
+    /**
+     * Errors on any option that wasn't read, since it's probably a typo.
+     */
+    pub fn finish(self) -> Result<(), String> {
+        let mut unknown: Vec<_> = self.values.into_keys().collect();
+        unknown.sort();
+        match unknown.first() {
+            Some(name) => Err(format!("Unknown option `--{}`", name)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse() {
+        let mut options = parse(&["--ticks", "10", "--out=log.jsonl"]).unwrap();
+        assert_eq!(options.take::<usize>("ticks"), Ok(Some(10)));
+        assert_eq!(options.take_string("out").as_deref(), Some("log.jsonl"));
+        assert_eq!(options.take::<usize>("seed"), Ok(None));
+        assert!(options.finish().is_ok());
+
+        let mut options = parse(&["--ticks", "ten", "--typo", "1"]).unwrap();
+        assert!(options.take::<usize>("ticks").is_err());
+        assert!(options.finish().is_err());
+
+        assert!(parse(&["ticks"]).is_err());
+        assert!(parse(&["--ticks"]).is_err());
+        assert!(parse(&["--ticks", "--out", "log"]).is_err());
+        assert!(parse(&["--seed", "1", "--seed", "2"]).is_err());
+    }
+}
@@ -0,0 +1,341 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use kennel_club::{
+    DEFAULT_HEATMAP_RESOLUTION, Event, ImageFormat, Kennel, Migration, Simulation, Snapshot, Stats,
+    Terminal,
+};
+use rand::rngs::SmallRng;
+use serde::Serialize;
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::{clear, cursor, terminal_size};
+
+use crate::cli::args::Options;
+use crate::cli::{Common, describe_migration, tick_duration, tui, watch_interval};
+
+/**
+ * How often the terminal renderer redraws, moving creatures smoothly between ticks.
+ */
+static FRAME_DURATION: Duration = Duration::from_millis(50);
+
+/**
+ * Shows `message` on the bottom row, which the terminal renderer keeps free for it
+ * so nothing is written over the kennel.
+ */
+fn draw_status<W: Write>(terminal: &mut Terminal<W>, message: &str) -> Result<(), String> {
+    let (columns, rows) = terminal.size();
+    let message: String = message
+        .chars()
+        .take(columns.saturating_sub(1) as usize)
+        .collect();
+    let out = terminal.get_mut();
+    write!(
+        out,
+        "{}{}{}",
+        cursor::Goto(1, rows + 1),
+        clear::CurrentLine,
+        message
+    )
+    .and_then(|_| out.flush())
+    .map_err(|e| format!("Unable to draw to the terminal. {}", e))
+}
+
+fn advance(mut kennel: Kennel, rng: &mut SmallRng, ticks: usize) -> Result<Kennel, String> {
+    for _ in 0..ticks {
+        kennel.tick(rng)?;
+    }
+    Ok(kennel)
+}
+
+fn write_file(path: &Path, data: &[u8]) -> Result<(), String> {
+    std::fs::write(path, data).map_err(|e| format!("Unable to write {}. {}", path.display(), e))
+}
+
+pub fn run(mut common: Common, mut options: Options) -> Result<(), String> {
+    let renderer = options
+        .take_string("renderer")
+        .unwrap_or("terminal".to_string());
+    let tick = tick_duration(&mut options)?;
+    let watch_interval = watch_interval(&mut options)?;
+    options.finish()?;
+
+    let (club, room) = common.load_watched()?;
+    let club = club.with_tick_duration(tick);
+    let mut watcher = common.watcher(watch_interval, &club, &room)?;
+    let mut simulation = Simulation::of_club(club, &room)?;
+    let mut rng = common.rng;
+    if renderer == "tui" {
+        return tui::run(simulation, rng, watcher);
+    }
+
+    let mut terminal = match renderer.as_str() {
+        "terminal" => Some(Terminal::new(tui::open_raw_screen()?)),
+        "text" | "counts" => None,
+        _ => {
+            return Err(format!(
+                "Unknown renderer `{}`, pick one of terminal, tui, text or counts",
+                renderer
+            ));
+        }
+    };
+
+    // the terminal redraws between ticks, the text renderers only when something moved
+    let frame_duration = match terminal {
+        Some(_) => FRAME_DURATION.min(tick),
+        None => tick,
+    };
+    let mut keys = terminal.as_ref().map(|_| termion::async_stdin().keys());
+    let mut status = String::new();
+    let mut last_frame = Instant::now();
+
+    loop {
+        match &mut terminal {
+            Some(terminal) => {
+                let (columns, rows) = terminal_size().unwrap_or((80, 24));
+                terminal.resize(columns, rows.saturating_sub(1).max(1));
+                simulation.draw(terminal)?;
+                draw_status(terminal, &status)?;
+            }
+            None if renderer == "counts" => simulation.kennel().pretty_print(),
+            None => simulation.kennel().print(),
+        }
+        sleep(frame_duration);
+
+        // returning drops the terminal, which puts the screen and cursor back
+        for key in keys.iter_mut().flatten() {
+            match key.map_err(|e| format!("Unable to read input. {}", e))? {
+                Key::Char('q') | Key::Ctrl('c') => return Ok(()),
+                _ => {}
+            }
+        }
+
+        let now = Instant::now();
+        simulation.advance(now - last_frame, &mut rng)?;
+        last_frame = now;
+        let mut messages: Vec<String> = simulation
+            .migrations()
+            .iter()
+            .map(describe_migration)
+            .collect();
+        messages.extend(
+            watcher
+                .as_mut()
+                .and_then(|watcher| watcher.reload(simulation.kennel_mut(), &mut rng)),
+        );
+
+        // anything printed over the terminal renderer's kennel would stay there,
+        // so its messages go on the status row instead
+        for message in messages {
+            match terminal {
+                Some(_) => status = message,
+                None => eprintln!("{}", message),
+            }
+        }
+    }
+}
+
+pub fn render(mut common: Common, mut options: Options) -> Result<(), String> {
+    let out: String = options.require("out")?;
+    let width = options.take("width")?.unwrap_or(1024);
+    let height = options.take("height")?.unwrap_or(1024);
+    let format = options
+        .take_string("format")
+        .or_else(|| {
+            Path::new(&out)
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_string())
+        })
+        .ok_or("Unable to tell the format from `--out`, set `--format`")?;
+    let ticks = options.take("ticks")?.unwrap_or(0);
+    let map = options.take_string("map");
+    options.finish()?;
+
+    let kennel = common.load()?;
+    let kennel = advance(kennel, &mut common.rng, ticks)?;
+
+    let data = match format.to_lowercase().as_str() {
+        "svg" => kennel.get_svg()?.into_bytes(),
+        _ => {
+            let image_format = ImageFormat::from_extension(&format)
+                .ok_or(format!("Unknown image format `{}`", format))?;
+            kennel.get_image(width, height, image_format)?
+        }
+    };
+    write_file(Path::new(&out), &data)?;
+
+    if let Some(map) = map {
+        let html = kennel.get_image_map(width, height, "kennel");
+        write_file(Path::new(&map), html.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+pub fn validate(mut common: Common, options: Options) -> Result<(), String> {
+    options.finish()?;
+
+    if common.room.is_some() {
+        let kennel = common.load()?;
+        println!(
+            "{} is ok, {} creatures",
+            common.data.display(),
+            kennel.creatures().len()
+        );
+        return Ok(());
+    }
+
+    let club = common.load_club()?;
+
+    let creatures = club.rooms().map(|(_, kennel)| kennel.creatures().len());
+    println!(
+        "{} is ok, {} creatures",
+        common.data.display(),
+        creatures.sum::<usize>()
+    );
+    if club.rooms().count() > 1 {
+        for (room, kennel) in club.rooms() {
+            println!("  room {}, {} creatures", room, kennel.creatures().len());
+        }
+        for door in club.doors() {
+            println!("  door between {} and {}", door.rooms[0], door.rooms[1]);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct LogLine<'a> {
+    tick: usize,
+    #[serde(flatten)]
+    snapshot: &'a Snapshot,
+    events: &'a [Event],
+    migrations: &'a [Migration],
+}
+
+/**
+ * Runs every room for a number of ticks without drawing, recording stats for the watched room
+ * as it goes and handing each tick to `on_tick`.
+ */
+fn simulate_ticks<F: FnMut(usize, &Simulation) -> Result<(), String>>(
+    common: &mut Common,
+    ticks: usize,
+    resolution: usize,
+    mut on_tick: F,
+) -> Result<Stats, String> {
+    let mut stats = Stats::new(resolution);
+    let (club, room) = common.load_watched()?;
+    let mut simulation = Simulation::of_club(club, &room)?;
+    on_tick(0, &simulation)?;
+    stats.record_start(simulation.kennel());
+
+    for tick in 1..=ticks {
+        simulation.step(&mut common.rng)?;
+        stats.record(simulation.kennel());
+        on_tick(tick, &simulation)?;
+    }
+
+    Ok(stats)
+}
+
+pub fn simulate(mut common: Common, mut options: Options) -> Result<(), String> {
+    let ticks: usize = options.require("ticks")?;
+    let out = options.take_string("out");
+    let stats_out = options.take_string("stats");
+    let heatmap_out = options.take_string("heatmap");
+    let resolution = options
+        .take("resolution")?
+        .unwrap_or(DEFAULT_HEATMAP_RESOLUTION);
+    options.finish()?;
+
+    if out.is_none() && stats_out.is_none() && heatmap_out.is_none() {
+        return Err("Nothing to write, set `--out`, `--stats` or `--heatmap`".to_string());
+    }
+    if resolution == 0 {
+        return Err("Option `--resolution` must be positive".to_string());
+    }
+
+    let mut log = out
+        .as_ref()
+        .map(|out| {
+            File::create(out)
+                .map(BufWriter::new)
+                .map_err(|e| format!("Unable to create {}. {}", out, e))
+        })
+        .transpose()?;
+    let write_error = |e: std::io::Error| format!("Unable to write the log. {}", e);
+
+    let stats = simulate_ticks(&mut common, ticks, resolution, |tick, simulation| {
+        let Some(log) = &mut log else {
+            return Ok(());
+        };
+
+        let snapshot = simulation.kennel().snapshot();
+        let line = serde_json::to_string(&LogLine {
+            tick,
+            snapshot: &snapshot,
+            events: simulation.events(),
+            migrations: simulation.migrations(),
+        })
+        .map_err(|e| format!("Unable to serialize tick {}. {}", tick, e))?;
+        writeln!(log, "{}", line).map_err(write_error)
+    })?;
+
+    if let Some(log) = &mut log {
+        log.flush().map_err(write_error)?;
+    }
+
+    if let Some(stats_out) = stats_out {
+        let json = serde_json::to_string_pretty(&stats)
+            .map_err(|e| format!("Unable to serialize stats. {}", e))?;
+        write_file(Path::new(&stats_out), json.as_bytes())?;
+    }
+
+    if let Some(heatmap_out) = heatmap_out {
+        stats
+            .heatmap
+            .to_image(512, 512)
+            .save(&heatmap_out)
+            .map_err(|e| format!("Unable to write {}. {}", heatmap_out, e))?;
+    }
+
+    Ok(())
+}
+
+pub fn stats(mut common: Common, mut options: Options) -> Result<(), String> {
+    let ticks: usize = options.take("ticks")?.unwrap_or(10_000);
+    options.finish()?;
+
+    let stats = simulate_ticks(
+        &mut common,
+        ticks,
+        DEFAULT_HEATMAP_RESOLUTION,
+        |_, _| Ok(()),
+    )?;
+
+    println!(
+        "{:<16} {:>7} {:>7} {:>7} {:>7} {:>9} {:>9} {:>6} {:>7}",
+        "creature", "idle", "sleep", "follow", "flee", "distance", "collided", "walls", "pinned"
+    );
+    let share = |count: usize| 100.0 * count as f64 / stats.ticks.max(1) as f64;
+    for (id, creature) in stats.creatures.iter() {
+        let states = &creature.ticks_in_state;
+        println!(
+            "{:<16} {:>6.1}% {:>6.1}% {:>6.1}% {:>6.1}% {:>9.2} {:>9} {:>6} {:>6.1}%",
+            id,
+            share(states.idle),
+            share(states.sleep),
+            share(states.follow),
+            share(states.flee),
+            creature.distance,
+            creature.collisions,
+            creature.wall_collisions,
+            share(creature.ticks_against_wall),
+        );
+    }
+
+    Ok(())
+}
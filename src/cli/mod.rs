@@ -0,0 +1,197 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use kennel_club::{DEFAULT_ROOM, Kennel, KennelClub, Migration};
+use rand::SeedableRng;
+use rand::rngs::SmallRng;
+
+use crate::cli::args::Options;
+use crate::cli::watch::Watcher;
+
+mod args;
+mod commands;
+mod tui;
+mod watch;
+
+static USAGE: &str = "\
+usage: kennel-club <command> [options]
+
+commands
+  run        watch the kennel, q quits the terminal and tui renderers
+               --renderer terminal|tui|text|counts   (default terminal)
+               --tick-ms N                           time simulated per tick (default 1000)
+               --watch-ms N                          reload the data directory when it changes,
+                                                     checking every N milliseconds (default off,
+                                                     only for a single room)
+  render     draw the kennel to a file
+               --out FILE                            png, jpg, gif, webp or svg
+               --width N --height N                  (default 1024)
+               --format FORMAT                       (default from --out)
+               --ticks N                             ticks to run first (default 0)
+               --map FILE                            also write an html image map
+  validate   check the data directory loads, and list its rooms
+  simulate   run without drawing
+               --ticks N
+               --out FILE                            log ticks and their events as json lines
+               --stats FILE                          write what the creatures did as json
+               --heatmap FILE                        draw where the creatures went
+               --resolution N                        heatmap cells per side (default 32)
+  stats      run without drawing and summarize what the creatures did
+               --ticks N                             (default 10000)
+
+every command takes
+  --data DIR                                         (default ./data)
+  --room NAME                                        the room of a data directory with rooms to use;
+                                                     run, simulate and stats still run every room
+                                                     and only watch this one (default the creatures
+                                                     without a room, or else the first room)
+  --seed N                                           (default random)
+";
+
+/**
+ * Options every command shares.
+ */
+pub struct Common {
+    pub data: PathBuf,
+    pub room: Option<String>,
+    pub rng: SmallRng,
+}
+
+impl Common {
+    fn take(options: &mut Options) -> Result<Self, String> {
+        let data = options
+            .take_string("data")
+            .map_or(PathBuf::from("./data"), PathBuf::from);
+        let room = options.take_string("room");
+        let rng = match options.take::<u64>("seed")? {
+            Some(seed) => SmallRng::seed_from_u64(seed),
+            None => SmallRng::from_rng(&mut rand::rng()),
+        };
+        Ok(Common { data, room, rng })
+    }
+
+    /**
+     * Loads just the room picked with `--room`, or the default one.
+     */
+    pub fn load(&mut self) -> Result<Kennel, String> {
+        let (mut club, room) = self.load_watched()?;
+        club.remove_room(&room).map_err(|e| {
+            format!(
+                "Unable to load the kennel in {}. {}",
+                self.data.display(),
+                e
+            )
+        })
+    }
+
+    pub fn load_club(&mut self) -> Result<KennelClub, String> {
+        KennelClub::load(&self.data, &mut self.rng)
+            .map_err(|e| format!("Unable to load the rooms in {}. {}", self.data.display(), e))
+    }
+
+    /**
+     * Loads every room, along with the name of the one to watch: the room picked with `--room`,
+     * or else the default room, or else the first one.
+     */
+    pub fn load_watched(&mut self) -> Result<(KennelClub, String), String> {
+        let club = self.load_club()?;
+        let room = match &self.room {
+            Some(room) if club.room(room).is_none() => {
+                return Err(format!("No room {} in {}", room, self.data.display()));
+            }
+            Some(room) => room.clone(),
+            None if club.room(DEFAULT_ROOM).is_some() => DEFAULT_ROOM.to_string(),
+            None => club
+                .rooms()
+                .next()
+                .map(|(room, _)| room.to_string())
+                .ok_or(format!("No rooms in {}", self.data.display()))?,
+        };
+        Ok((club, room))
+    }
+
+    /**
+     * The directory a room is loaded from on its own, if it isn't picked out of the top-level
+     * metadata by its creatures' `room` field.
+     */
+    fn room_dir(&self, room: &str) -> Option<PathBuf> {
+        let subdirectory = self.data.join(room);
+        if subdirectory.join("metadata.json").is_file() {
+            return Some(subdirectory);
+        }
+        (room == DEFAULT_ROOM).then(|| self.data.clone())
+    }
+
+    /**
+     * Watches the directory `room` reloads from, every `interval` if there is one.
+     */
+    pub fn watcher(
+        &self,
+        interval: Option<Duration>,
+        club: &KennelClub,
+        room: &str,
+    ) -> Result<Option<Watcher>, String> {
+        let Some(interval) = interval else {
+            return Ok(None);
+        };
+        if club.rooms().count() > 1 {
+            return Err("Option `--watch-ms` doesn't work with rooms yet".to_string());
+        }
+
+        let dir = self.room_dir(room).ok_or(format!(
+            "Option `--watch-ms` doesn't work yet with room {}, named by its creatures' metadata",
+            room
+        ))?;
+        Ok(Some(Watcher::new(&dir, interval)))
+    }
+}
+
+pub fn tick_duration(options: &mut Options) -> Result<Duration, String> {
+    match options.take::<u64>("tick-ms")? {
+        Some(0) => Err("Option `--tick-ms` must be positive".to_string()),
+        Some(millis) => Ok(Duration::from_millis(millis)),
+        None => Ok(Duration::from_secs(1)),
+    }
+}
+
+pub fn watch_interval(options: &mut Options) -> Result<Option<Duration>, String> {
+    match options.take::<u64>("watch-ms")? {
+        Some(0) => Err("Option `--watch-ms` must be positive".to_string()),
+        Some(millis) => Ok(Some(Duration::from_millis(millis))),
+        None => Ok(None),
+    }
+}
+
+pub fn describe_migration(migration: &Migration) -> String {
+    format!(
+        "{} wandered from {} to {}",
+        migration.id, migration.from, migration.to
+    )
+}
+
+/**
+ * Runs the command in the arguments, not including the program name.
+ */
+pub fn run<I: Iterator<Item = String>>(mut args: I) -> Result<(), String> {
+    let command = match args.next() {
+        Some(command) if command != "--help" && command != "-h" && command != "help" => command,
+        _ => {
+            print!("{}", USAGE);
+            return Ok(());
+        }
+    };
+
+    let mut options = Options::parse(args)?;
+    let common = Common::take(&mut options)?;
+    match command.as_str() {
+        "run" => commands::run(common, options),
+        "render" => commands::render(common, options),
+        "validate" => commands::validate(common, options),
+        "simulate" => commands::simulate(common, options),
+        "stats" => commands::stats(common, options),
+        _ => Err(format!(
+            "Unknown command `{}`, see `kennel-club --help`",
+            command
+        )),
+    }
+}
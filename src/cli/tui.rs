@@ -0,0 +1,369 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{Stdout, Write};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use kennel_club::creature::{Creature, State};
+use kennel_club::math::Vec2;
+use kennel_club::{DEFAULT_ATTRACTOR_TICKS, DEFAULT_TREAT_TICKS, Simulation, Terminal};
+use rand::Rng;
+use rand::rngs::SmallRng;
+use termion::event::{Event, Key, MouseButton, MouseEvent};
+use termion::input::{MouseTerminal, TermRead};
+use termion::raw::{IntoRawMode, RawTerminal};
+use termion::screen::{AlternateScreen, IntoAlternateScreen};
+use termion::{clear, cursor, style, terminal_size};
+
+use crate::cli::describe_migration;
+use crate::cli::watch::Watcher;
+
+pub type RawScreen = cursor::HideCursor<AlternateScreen<RawTerminal<Stdout>>>;
+type Screen = MouseTerminal<RawScreen>;
+
+/**
+ * Columns on the right kept for the status panel.
+ */
+static PANEL_WIDTH: u16 = 34;
+
+/**
+ * How often input is polled and the screen redrawn, whatever the tick rate.
+ */
+static FRAME_DURATION: Duration = Duration::from_millis(50);
+
+static MIN_TICK_INTERVAL: Duration = Duration::from_millis(50);
+static MAX_TICK_INTERVAL: Duration = Duration::from_secs(10);
+
+/**
+ * How long a state forced from the keyboard holds, in ticks.
+ */
+static FORCE_TICKS: usize = 30;
+
+/**
+ * How many state changes are remembered per creature.
+ */
+static HISTORY_LENGTH: usize = 8;
+
+/**
+ * How hard balls pull and scares push creatures placed with the mouse.
+ */
+static ATTRACTOR_STRENGTH: f64 = 2.0;
+
+static HELP: [&str; 8] = [
+    "space  pause / resume",
+    "n      step once",
+    "+ -    faster / slower",
+    "tab    select creature",
+    "s f i  force sleep/follow/idle",
+    "r      release    t  treat",
+    "click  treat here q  quit",
+    "right  ball  middle  scare",
+];
+
+struct App {
+    simulation: Simulation,
+    rng: SmallRng,
+    paused: bool,
+    tick_interval: Duration,
+    last_tick: Instant,
+    ticks: usize,
+    selected: Option<String>,
+    history: HashMap<String, VecDeque<(usize, State)>>,
+    message: String,
+}
+
+impl App {
+    fn new(simulation: Simulation, rng: SmallRng) -> Self {
+        App {
+            tick_interval: simulation.kennel().tick_duration(),
+            simulation,
+            rng,
+            paused: false,
+            last_tick: Instant::now(),
+            ticks: 0,
+            selected: None,
+            history: HashMap::new(),
+            message: String::new(),
+        }
+    }
+
+    fn step(&mut self) -> Result<(), String> {
+        self.simulation.step(&mut self.rng)?;
+        self.ticks += 1;
+        self.last_tick = Instant::now();
+        if let Some(migration) = self.simulation.migrations().last() {
+            self.message = describe_migration(migration);
+        }
+
+        for creature in self.simulation.kennel().creatures() {
+            let history = self.history.entry(creature.id.clone()).or_default();
+            if history.back().map(|(_, state)| state) != Some(&creature.creature_state) {
+                history.push_back((self.ticks, creature.creature_state.clone()));
+                if history.len() > HISTORY_LENGTH {
+                    history.pop_front();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn selected_creature(&self) -> Option<&Creature> {
+        let id = self.selected.as_ref()?;
+        self.simulation
+            .kennel()
+            .creatures()
+            .into_iter()
+            .find(|creature| &creature.id == id)
+    }
+
+    fn select(&mut self, offset: isize) {
+        let ids: Vec<String> = self
+            .simulation
+            .kennel()
+            .creatures()
+            .iter()
+            .map(|creature| creature.id.clone())
+            .collect();
+        if ids.is_empty() {
+            return;
+        }
+
+        let current = self
+            .selected
+            .as_ref()
+            .and_then(|id| ids.iter().position(|other| other == id));
+        let next = match current {
+            Some(idx) => (idx as isize + offset).rem_euclid(ids.len() as isize) as usize,
+            None if offset < 0 => ids.len() - 1,
+            None => 0,
+        };
+        self.selected = Some(ids[next].clone());
+    }
+
+    fn force(&mut self, state: State, ticks: usize) {
+        let Some(id) = self.selected.clone() else {
+            self.message = "Select a creature first".to_string();
+            return;
+        };
+
+        self.message = match self
+            .simulation
+            .kennel_mut()
+            .force_state(&id, state.clone(), ticks)
+        {
+            Ok(()) if ticks == 0 => format!("Released {}", id),
+            Ok(()) => format!("{} is held in {:?}", id, state),
+            Err(e) => e,
+        };
+    }
+
+    fn drop_treat(&mut self, position: Vec2) {
+        self.message = match self
+            .simulation
+            .kennel_mut()
+            .drop_treat(position, DEFAULT_TREAT_TICKS)
+        {
+            Ok(()) => format!("Dropped a treat at ({:.2}, {:.2})", position.x, position.y),
+            Err(e) => e,
+        };
+    }
+
+    fn place_attractor(&mut self, position: Vec2, repels: bool) {
+        let placed = if repels {
+            self.simulation.kennel_mut().place_repeller(
+                position,
+                ATTRACTOR_STRENGTH,
+                DEFAULT_ATTRACTOR_TICKS,
+            )
+        } else {
+            self.simulation.kennel_mut().place_attractor(
+                position,
+                ATTRACTOR_STRENGTH,
+                DEFAULT_ATTRACTOR_TICKS,
+            )
+        };
+        self.message = match placed {
+            Ok(()) if repels => format!("Scared them off ({:.2}, {:.2})", position.x, position.y),
+            Ok(()) => format!("Threw a ball to ({:.2}, {:.2})", position.x, position.y),
+            Err(e) => e,
+        };
+    }
+
+    /**
+     * Handles an input event, returning false once it's time to quit.
+     */
+    fn handle<W: Write>(&mut self, event: Event, terminal: &Terminal<W>) -> Result<bool, String> {
+        match event {
+            Event::Key(Key::Char('q')) | Event::Key(Key::Ctrl('c')) => return Ok(false),
+            Event::Key(Key::Char(' ')) => self.paused = !self.paused,
+            Event::Key(Key::Char('n')) => self.step()?,
+            Event::Key(Key::Char('+')) | Event::Key(Key::Char('=')) => {
+                self.tick_interval = (self.tick_interval / 2).max(MIN_TICK_INTERVAL)
+            }
+            Event::Key(Key::Char('-')) => {
+                self.tick_interval = (self.tick_interval * 2).min(MAX_TICK_INTERVAL)
+            }
+            Event::Key(Key::Char('\t')) => self.select(1),
+            Event::Key(Key::BackTab) => self.select(-1),
+            Event::Key(Key::Esc) => self.selected = None,
+            Event::Key(Key::Char('s')) => self.force(State::Sleep, FORCE_TICKS),
+            Event::Key(Key::Char('f')) => self.force(State::Follow, FORCE_TICKS),
+            Event::Key(Key::Char('i')) => self.force(State::Idle, FORCE_TICKS),
+            Event::Key(Key::Char('r')) => self.force(State::Idle, 0),
+            Event::Key(Key::Char('t')) => {
+                let position = Vec2::new(self.rng.random(), self.rng.random());
+                self.drop_treat(position);
+            }
+            Event::Mouse(MouseEvent::Press(MouseButton::Left, column, row)) => {
+                self.drop_treat(terminal.cell_to_world(column, row))
+            }
+            Event::Mouse(MouseEvent::Press(MouseButton::Right, column, row)) => {
+                self.place_attractor(terminal.cell_to_world(column, row), false)
+            }
+            Event::Mouse(MouseEvent::Press(MouseButton::Middle, column, row)) => {
+                self.place_attractor(terminal.cell_to_world(column, row), true)
+            }
+            _ => {}
+        }
+
+        Ok(true)
+    }
+
+    fn panel_lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("{}kennel club{}", style::Bold, style::Reset),
+            format!(
+                "{} tick {} every {}ms",
+                if self.paused { "paused " } else { "running" },
+                self.ticks,
+                self.tick_interval.as_millis()
+            ),
+            format!(
+                "treats: {} attractors: {}",
+                self.simulation.kennel().treats().len(),
+                self.simulation.kennel().attractors().len()
+            ),
+            String::new(),
+        ];
+
+        match self.selected_creature() {
+            Some(creature) => {
+                let forced = match &creature.forced_state {
+                    Some((_, ticks)) => format!(" (held {})", ticks),
+                    None => String::new(),
+                };
+                let with = match &creature.pairing {
+                    Some(pairing) => format!("{:?} with {}", pairing.interaction, pairing.partner),
+                    None => "nobody".to_string(),
+                };
+                lines.extend([
+                    format!("{}{}{}", style::Bold, creature.display_name, style::Reset),
+                    format!("id       {}", creature.id),
+                    format!("url      {}", creature.url),
+                    format!("radius   {:.3}", creature.radius),
+                    format!("step     {:.3}", creature.step_size),
+                    format!("state    {:?}{}", creature.creature_state, forced),
+                    format!("sprite   {}", creature.sprite_state),
+                    format!("with     {}", with),
+                    format!(
+                        "position ({:.2}, {:.2})",
+                        creature.position.x, creature.position.y
+                    ),
+                    "history".to_string(),
+                ]);
+                let history = self.history.get(&creature.id).into_iter().flatten();
+                lines.extend(
+                    history
+                        .rev()
+                        .map(|(tick, state)| format!("  {:>6} {:?}", tick, state)),
+                );
+            }
+            None => lines.push("tab to select a creature".to_string()),
+        }
+
+        lines.push(String::new());
+        lines.push(self.message.clone());
+        lines
+    }
+
+    fn draw_panel(&self, out: &mut Screen, left: u16, rows: u16) -> std::io::Result<()> {
+        let help_top = rows.saturating_sub(HELP.len() as u16);
+        let lines = self.panel_lines();
+        for row in 0..rows {
+            let line = match row.checked_sub(help_top) {
+                Some(help_row) => HELP[help_row as usize],
+                None => lines.get(row as usize).map_or("", String::as_str),
+            };
+            write!(
+                out,
+                "{}{}{}",
+                cursor::Goto(left, row + 1),
+                clear::UntilNewline,
+                line
+            )?;
+        }
+        out.flush()
+    }
+}
+
+/**
+ * Switches stdout to the alternate screen in raw mode, so Ctrl-C comes in as a key
+ * instead of killing the process before the screen and cursor are restored.
+ */
+pub fn open_raw_screen() -> Result<RawScreen, String> {
+    let raw = std::io::stdout()
+        .into_raw_mode()
+        .map_err(|e| format!("Unable to switch the terminal to raw mode. {}", e))?;
+    let screen = raw
+        .into_alternate_screen()
+        .map_err(|e| format!("Unable to switch to the alternate screen. {}", e))?;
+    Ok(cursor::HideCursor::from(screen))
+}
+
+fn open_screen() -> Result<Screen, String> {
+    Ok(MouseTerminal::from(open_raw_screen()?))
+}
+
+/**
+ * Watch the simulation's room and poke at it from the keyboard until `q` is pressed.
+ */
+pub fn run(
+    simulation: Simulation,
+    rng: SmallRng,
+    mut watcher: Option<Watcher>,
+) -> Result<(), String> {
+    let mut app = App::new(simulation, rng);
+
+    let mut terminal = Terminal::new(open_screen()?);
+    let mut events = termion::async_stdin().events();
+
+    loop {
+        let (columns, rows) = terminal_size().unwrap_or((80, 24));
+        let kennel_size = (columns.saturating_sub(PANEL_WIDTH).max(1), rows);
+        terminal.resize(kennel_size.0, kennel_size.1);
+
+        for event in events.by_ref() {
+            let event = event.map_err(|e| format!("Unable to read input. {}", e))?;
+            if !app.handle(event, &terminal)? {
+                return Ok(());
+            }
+        }
+
+        if !app.paused && app.last_tick.elapsed() >= app.tick_interval {
+            app.step()?;
+        }
+
+        if let Some(message) = watcher
+            .as_mut()
+            .and_then(|watcher| watcher.reload(app.simulation.kennel_mut(), &mut app.rng))
+        {
+            app.message = message;
+        }
+
+        app.simulation.kennel().draw(&mut terminal)?;
+        app.draw_panel(terminal.get_mut(), kennel_size.0 + 2, rows)
+            .map_err(|e| format!("Unable to draw to the terminal. {}", e))?;
+
+        sleep(FRAME_DURATION);
+    }
+}
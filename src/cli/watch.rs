@@ -0,0 +1,87 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use kennel_club::{Kennel, Reload};
+use rand::Rng;
+
+/**
+ * Notices changes to the data directory by polling for the newest modification time in it.
+ * Directories count too, so removing a file shows up as a change.
+ */
+pub struct Watcher {
+    dir: PathBuf,
+    interval: Duration,
+    last_poll: Instant,
+    last_modified: Option<SystemTime>,
+}
+
+fn last_modified(path: &Path) -> Option<SystemTime> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok();
+    if !metadata.is_dir() {
+        return modified;
+    }
+
+    std::fs::read_dir(path)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| last_modified(&entry.path()))
+        .chain(modified)
+        .max()
+}
+
+impl Watcher {
+    pub fn new(dir: &Path, interval: Duration) -> Self {
+        Watcher {
+            dir: dir.to_path_buf(),
+            interval,
+            last_poll: Instant::now(),
+            last_modified: last_modified(dir),
+        }
+    }
+
+    /**
+     * Whether anything changed since the last poll.
+     * Polls at most once per interval, and is false in between.
+     */
+    pub fn poll(&mut self) -> bool {
+        if self.last_poll.elapsed() < self.interval {
+            return false;
+        }
+        self.last_poll = Instant::now();
+
+        let modified = last_modified(&self.dir);
+        let changed = modified != self.last_modified;
+        self.last_modified = modified;
+        changed
+    }
+
+    /**
+     * Reloads the kennel if the data directory changed, describing what happened.
+     */
+    pub fn reload<R: Rng + ?Sized>(&mut self, kennel: &mut Kennel, rng: &mut R) -> Option<String> {
+        if !self.poll() {
+            return None;
+        }
+
+        Some(match kennel.reload(&self.dir, rng) {
+            Ok(reload) => describe(&reload),
+            Err(e) => format!("Unable to reload {}. {}", self.dir.display(), e),
+        })
+    }
+}
+
+fn describe(reload: &Reload) -> String {
+    let mut changes: Vec<String> = vec![];
+    if !reload.added.is_empty() {
+        changes.push(format!("added {}", reload.added.join(", ")));
+    }
+    if !reload.removed.is_empty() {
+        changes.push(format!("removed {}", reload.removed.join(", ")));
+    }
+
+    match changes.is_empty() {
+        true => format!("Reloaded {} creatures", reload.updated.len()),
+        false => format!("Reloaded, {}", changes.join(", ")),
+    }
+}
@@ -0,0 +1,114 @@
+use serde::Deserialize;
+
+use crate::math::Vec2;
+
+/**
+ * How long a command lasts if it doesn't say.
+ */
+pub static DEFAULT_COMMAND_TICKS: usize = 10;
+
+/**
+ * The longest a single command can hold a creature, so nobody parks theirs for good.
+ */
+pub static MAX_COMMAND_TICKS: usize = 60;
+
+fn default_ticks() -> usize {
+    DEFAULT_COMMAND_TICKS
+}
+
+/**
+ * Something an owner tells their creature to do, over its own random behaviour.
+ */
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Command {
+    Sleep {
+        #[serde(default = "default_ticks")]
+        ticks: usize,
+    },
+    Wave {
+        #[serde(default = "default_ticks")]
+        ticks: usize,
+    },
+    /**
+     * Walks to the position, giving up if it takes longer than `ticks`.
+     */
+    GoTo {
+        position: Vec2,
+        #[serde(default = "default_ticks")]
+        ticks: usize,
+    },
+}
+
+impl Command {
+    pub fn ticks(&self) -> usize {
+        match self {
+            Command::Sleep { ticks } | Command::Wave { ticks } | Command::GoTo { ticks, .. } => {
+                *ticks
+            }
+        }
+    }
+
+    fn ticks_mut(&mut self) -> &mut usize {
+        match self {
+            Command::Sleep { ticks } | Command::Wave { ticks } | Command::GoTo { ticks, .. } => {
+                ticks
+            }
+        }
+    }
+
+    /**
+     * Counts down a tick of the command, returning whether there's any of it left.
+     */
+    pub(crate) fn count_down(&mut self) -> bool {
+        let ticks = self.ticks_mut();
+        *ticks = ticks.saturating_sub(1);
+        *ticks > 0
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let ticks = self.ticks();
+        if ticks == 0 || ticks > MAX_COMMAND_TICKS {
+            return Err(format!(
+                "Commands last between 1 and {} ticks, not {}",
+                MAX_COMMAND_TICKS, ticks
+            ));
+        }
+
+        if let Command::GoTo { position, .. } = self
+            && !((0.0..=1.0).contains(&position.x) && (0.0..=1.0).contains(&position.y))
+        {
+            return Err(format!(
+                "Position ({}, {}) is outside the kennel",
+                position.x, position.y
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize() {
+        let command: Command =
+            serde_json::from_str(r#"{ "action": "go_to", "position": { "x": 0.2, "y": 0.8 } }"#)
+                .expect("Command must deserialize");
+        assert_eq!(
+            command,
+            Command::GoTo {
+                position: Vec2::new(0.2, 0.8),
+                ticks: DEFAULT_COMMAND_TICKS
+            }
+        );
+        assert!(command.validate().is_ok());
+
+        let command: Command = serde_json::from_str(r#"{ "action": "sleep", "ticks": 1000 }"#)
+            .expect("Command must deserialize");
+        assert!(command.validate().is_err());
+        assert!(serde_json::from_str::<Command>(r#"{ "action": "explode" }"#).is_err());
+    }
+}
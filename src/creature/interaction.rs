@@ -0,0 +1,51 @@
+use serde::Serialize;
+
+use crate::creature::State;
+
+/**
+ * Something two creatures do together for a while.
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Interaction {
+    /**
+     * Both stop and sniff each other.
+     */
+    Sniff,
+    /**
+     * The leader chases the other around.
+     */
+    Chase,
+    /**
+     * Both curl up for a nap together.
+     */
+    Nap,
+}
+
+impl Interaction {
+    /**
+     * The state a creature is in while taking part.
+     */
+    pub fn state(&self, leads: bool) -> State {
+        match (self, leads) {
+            (Interaction::Sniff, _) => State::Idle,
+            (Interaction::Chase, true) => State::Follow,
+            (Interaction::Chase, false) => State::Flee,
+            (Interaction::Nap, _) => State::Sleep,
+        }
+    }
+}
+
+/**
+ * A creature's side of an interaction.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Pairing {
+    pub interaction: Interaction,
+    pub partner: String,
+    /**
+     * Whether this creature started it, which decides who chases whom.
+     */
+    pub leads: bool,
+    pub ticks_left: usize,
+}
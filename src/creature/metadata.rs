@@ -20,6 +20,11 @@ pub struct Metadata {
     pub sprite_loader: sprite::Loader,
     #[serde(default)]
     pub initial_state: State,
+    /**
+     * Which room of a kennel club the creature lives in, if the data directory has rooms.
+     */
+    #[serde(default)]
+    pub room: Option<String>,
 }
 
 impl Metadata {
@@ -41,6 +46,7 @@ impl Metadata {
             url: "".to_string(),
             sprite_loader: sprite::Loader::new(),
             initial_state: State::Idle,
+            room: None,
         }
     }
 }
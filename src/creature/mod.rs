@@ -1,17 +1,24 @@
+use std::collections::VecDeque;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 
+pub use command::{Command, DEFAULT_COMMAND_TICKS, MAX_COMMAND_TICKS};
+pub use interaction::{Interaction, Pairing};
 pub use metadata::Metadata;
 use rand::Rng;
 pub use state::State;
 
-use crate::physics::Step;
+use crate::physics::{DISTANCE_TOLERANCE, Step};
 use crate::{Sprite, sprite};
 use crate::{math::Vec2, physics::Collidable};
 
+mod command;
+mod interaction;
 mod metadata;
 mod state;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Creature {
     pub id: String,
     pub display_name: String,
@@ -22,13 +29,42 @@ pub struct Creature {
     pub position: Vec2,
     pub sprite_state: sprite::State,
     pub sprite_state_duration: usize,
-    pub sprite_sheet: sprite::Sheet,
+    pub sprite_state_elapsed: Duration,
+    /**
+     * The previous sprite state while the transition out of it is still playing.
+     */
+    pub sprite_transition: Option<sprite::State>,
+    pub sprite_sheet: Arc<sprite::Sheet>,
+    /**
+     * A state the creature is held in instead of rolling a new one, and for how many more ticks.
+     */
+    pub forced_state: Option<(State, usize)>,
+    /**
+     * What the creature is doing with another creature, if anything.
+     */
+    pub pairing: Option<Pairing>,
+    /**
+     * What the creature's owner told it to do, first come first served.
+     */
+    pub commands: VecDeque<Command>,
 }
 
 #[cfg(test)]
 impl From<Metadata> for Creature {
     fn from(metadata: Metadata) -> Self {
-        let sprite_sheet = sprite::Sheet::new();
+        // a blank square sprite for every state, so mocks can be placed and drawn
+        let mut animation = sprite::Animation::new();
+        let blank = image::RgbaImage::new(1, 1).into();
+        animation.push(
+            Sprite::from_image(blank, image::ImageFormat::Png),
+            Duration::from_secs(1),
+        );
+        let mut sprite_sheet = sprite::Sheet::new();
+        sprite_sheet.set(&sprite::State::Idle, animation);
+        sprite_sheet
+            .derive_missing()
+            .expect("Idle is enough to derive everything");
+        let sprite_sheet = Arc::new(sprite_sheet);
         Creature {
             id: metadata.id,
             display_name: metadata.display_name,
@@ -39,15 +75,24 @@ impl From<Metadata> for Creature {
             position: Vec2::zero(),
             sprite_state: sprite::State::Idle,
             sprite_state_duration: 0,
+            sprite_state_elapsed: Duration::ZERO,
+            sprite_transition: None,
             sprite_sheet,
+            forced_state: None,
+            pairing: None,
+            commands: VecDeque::new(),
         }
     }
 }
 
 impl Creature {
-    pub fn load(metadata: Metadata, data_dir: &Path) -> Self {
-        let sprite_sheet = metadata.sprite_loader.load(&data_dir.join(&metadata.id));
-        Creature {
+    pub fn load(metadata: Metadata, data_dir: &Path) -> Result<Self, String> {
+        let sprite_sheet = metadata
+            .sprite_loader
+            .load(&data_dir.join(&metadata.id))
+            .map(Arc::new)
+            .map_err(|e| format!("Unable to load sprites for creature {}. {}", metadata.id, e))?;
+        Ok(Creature {
             id: metadata.id,
             display_name: metadata.display_name,
             radius: metadata.radius,
@@ -57,8 +102,13 @@ impl Creature {
             position: Vec2::zero(),
             sprite_state: sprite::State::Idle,
             sprite_state_duration: 0,
+            sprite_state_elapsed: Duration::ZERO,
+            sprite_transition: None,
             sprite_sheet,
-        }
+            forced_state: None,
+            pairing: None,
+            commands: VecDeque::new(),
+        })
     }
 
     /**
@@ -67,51 +117,123 @@ impl Creature {
      * THE SPRITE STATE DOES NOT CHANGE.
      */
     pub fn with_next_state<R: Rng + ?Sized>(&self, rng: &mut R) -> Self {
-        let next_state = self.creature_state.next(rng);
-        Creature {
-            id: self.id.clone(),
-            display_name: self.display_name.clone(),
-            radius: self.radius,
-            step_size: self.step_size,
-            url: self.url.clone(),
-            creature_state: next_state,
-            position: self.position,
-            sprite_state: self.sprite_state,
-            sprite_state_duration: self.sprite_state_duration,
-            sprite_sheet: self.sprite_sheet.clone(),
-        }
+        let mut creature = self.clone();
+        creature.advance_state(rng);
+        creature
+    }
+
+    /**
+     * Same as `with_next_state`, but changes the creature in place.
+     * A forced state wins over an owner's command, which wins over an interaction.
+     * Either of the first two ends an interaction.
+     */
+    pub fn advance_state<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let is_commanded = self.forced_state.is_some() || !self.commands.is_empty();
+        self.pairing = self
+            .pairing
+            .take()
+            .filter(|pairing| pairing.ticks_left > 0 && !is_commanded);
+
+        let command = self.commands.front_mut();
+        let (next_state, forced_state) = match (&self.forced_state, command, &mut self.pairing) {
+            (Some((state, ticks)), _, _) => (state.clone(), Some((state.clone(), ticks - 1))),
+            (None, Some(command), _) => {
+                command.count_down();
+                let state = match command {
+                    Command::Sleep { .. } => State::Sleep,
+                    Command::Wave { .. } => State::Idle,
+                    Command::GoTo { .. } => State::Follow,
+                };
+                (state, None)
+            }
+            (None, None, Some(pairing)) => {
+                pairing.ticks_left -= 1;
+                (pairing.interaction.state(pairing.leads), None)
+            }
+            (None, None, None) => (self.creature_state.next(rng), None),
+        };
+        self.creature_state = next_state;
+        self.forced_state = forced_state.filter(|(_, ticks)| *ticks > 0);
+    }
+
+    /**
+     * The owner's command the creature is carrying out, unless it's being forced into something.
+     */
+    pub fn active_command(&self) -> Option<&Command> {
+        self.commands
+            .front()
+            .filter(|_| self.forced_state.is_none())
     }
 
     /**
-     * Has the creature take a step in the direction.
+     * Has the creature take a step in the direction over the `tick`.
      * Changes the sprite.
      */
-    pub fn step(self, step: Step) -> Self {
+    pub fn step(mut self, step: Step, tick: Duration) -> Self {
+        self.take_step(&step, tick);
+        self
+    }
+
+    /**
+     * Same as `step`, but changes the creature in place.
+     */
+    pub fn take_step(&mut self, step: &Step, tick: Duration) {
+        let interaction = self.pairing.as_ref().map(|pairing| pairing.interaction);
+        let is_waving = matches!(self.active_command(), Some(Command::Wave { .. }));
         let new_sprite_state = match sprite::State::from_delta(&step.delta) {
             Some(s) => s,
+            None if is_waving => sprite::State::Wave,
+            None if interaction == Some(Interaction::Sniff) => sprite::State::Sniff,
+            None if interaction == Some(Interaction::Nap) => sprite::State::Nap,
             None if self.creature_state == State::Sleep => sprite::State::Sleep,
             None => sprite::State::Idle,
         };
 
-        let new_sprite_state_duration = if new_sprite_state == self.sprite_state {
-            self.sprite_state_duration + 1
-        } else {
-            0
-        };
+        let (new_sprite_state_duration, mut new_sprite_state_elapsed, mut new_sprite_transition) =
+            if new_sprite_state == self.sprite_state {
+                (
+                    self.sprite_state_duration + 1,
+                    self.sprite_state_elapsed + tick,
+                    self.sprite_transition,
+                )
+            } else {
+                let transition = self
+                    .sprite_sheet
+                    .transition(&self.sprite_state, &new_sprite_state)
+                    .map(|_| self.sprite_state);
+                (0, Duration::ZERO, transition)
+            };
 
-        let new_position = step.resolve().position;
-        Creature {
-            id: self.id,
-            display_name: self.display_name,
-            radius: self.radius,
-            step_size: self.step_size,
-            creature_state: self.creature_state,
-            url: self.url,
-            position: new_position,
-            sprite_state: new_sprite_state,
-            sprite_state_duration: new_sprite_state_duration,
-            sprite_sheet: self.sprite_sheet,
+        // once the transition has played out, the state's own loop starts from its first frame
+        let transition_duration = new_sprite_transition
+            .and_then(|from| self.sprite_sheet.transition(&from, &new_sprite_state))
+            .map(sprite::Animation::duration);
+        if let Some(transition_duration) = transition_duration
+            && new_sprite_state_elapsed >= transition_duration
+        {
+            new_sprite_state_elapsed -= transition_duration;
+            new_sprite_transition = None;
+        }
+
+        self.position = step.resolve().position;
+
+        // commands that ran their course, or walks that arrived, make way for the next one
+        while let Some(command) = self.commands.front() {
+            let has_arrived = match command {
+                Command::GoTo { position, .. } => {
+                    (position - &self.position).squared_norm() < DISTANCE_TOLERANCE
+                }
+                _ => false,
+            };
+            if command.ticks() > 0 && !has_arrived {
+                break;
+            }
+            self.commands.pop_front();
         }
+        self.sprite_state = new_sprite_state;
+        self.sprite_state_duration = new_sprite_state_duration;
+        self.sprite_state_elapsed = new_sprite_state_elapsed;
+        self.sprite_transition = new_sprite_transition;
     }
 
     /**
@@ -128,34 +250,195 @@ impl Creature {
             position,
             sprite_state: self.sprite_state,
             sprite_state_duration: self.sprite_state_duration,
+            sprite_state_elapsed: self.sprite_state_elapsed,
+            sprite_transition: self.sprite_transition,
             sprite_sheet: self.sprite_sheet,
+            forced_state: self.forced_state,
+            pairing: self.pairing,
+            commands: self.commands,
+        }
+    }
+
+    /**
+     * Takes the metadata and sprites of a freshly loaded version of the creature,
+     * keeping where it is and what it's doing.
+     */
+    pub fn with_metadata_of(self, loaded: Creature) -> Self {
+        Creature {
+            id: loaded.id,
+            display_name: loaded.display_name,
+            radius: loaded.radius,
+            step_size: loaded.step_size,
+            url: loaded.url,
+            creature_state: self.creature_state,
+            position: self.position,
+            sprite_state: self.sprite_state,
+            sprite_state_duration: self.sprite_state_duration,
+            sprite_state_elapsed: self.sprite_state_elapsed,
+            sprite_transition: self.sprite_transition,
+            sprite_sheet: loaded.sprite_sheet,
+            forced_state: self.forced_state,
+            pairing: self.pairing,
+            commands: self.commands,
+        }
+    }
+
+    /**
+     * Holds the creature in a state for the next `ticks` ticks, starting now.
+     * Zero ticks lets it roll its own states again.
+     */
+    pub fn force_state(&mut self, state: State, ticks: usize) {
+        if ticks > 0 {
+            self.creature_state = state.clone();
         }
+        self.forced_state = Some((state, ticks)).filter(|(_, ticks)| *ticks > 0);
     }
 
     /**
-     * Calculates the next step given the creature's position
+     * Calculates the next step over the `tick` given the creature's position
      * and a center of mass to trend toward.
+     * `step_size` is how far the creature walks in a second.
+     * Following creatures go for the treat instead if there is one,
+     * stopping on it rather than overshooting.
+     * Otherwise `pull`, e.g. from attractors, bends the way they follow or flee.
+     * An owner's command overrides all of that.
      */
-    pub fn get_next_step(&self, center_of_mass: &Vec2) -> Step {
-        match self.creature_state {
-            State::Follow => {
-                let delta = center_of_mass - &self.position;
-                Step::new(self.as_collidable(), delta.with_norm(self.step_size))
-            }
-            State::Flee => {
-                let delta = &self.position - center_of_mass;
-                Step::new(self.as_collidable(), delta.with_norm(self.step_size))
+    pub fn get_next_step(
+        &self,
+        center_of_mass: &Vec2,
+        treat: Option<&Vec2>,
+        pull: &Vec2,
+        tick: Duration,
+    ) -> Step {
+        let step_size = self.step_size * tick.as_secs_f64();
+        match self.active_command() {
+            Some(Command::GoTo { position, .. }) => return self.step_to(position, step_size),
+            Some(_) => return Step::new(self.as_collidable(), Vec2::zero()),
+            None => (),
+        }
+
+        match (&self.creature_state, treat) {
+            (State::Follow, Some(treat)) => self.step_to(treat, step_size),
+            (State::Follow, None) => {
+                self.step_along(center_of_mass - &self.position, pull, step_size)
             }
+            (State::Flee, _) => self.step_along(&self.position - center_of_mass, pull, step_size),
             _ => Step::new(self.as_collidable(), Vec2::zero()),
         }
     }
 
+    /**
+     * A full step heading along `direction`, bent by `pull`.
+     * A pull of one weighs as much as the direction itself.
+     */
+    fn step_along(&self, direction: Vec2, pull: &Vec2, step_size: f64) -> Step {
+        let heading = if *pull == Vec2::zero() {
+            direction
+        } else if direction.squared_norm() == 0.0 {
+            *pull
+        } else {
+            &direction.normalized() + pull
+        };
+        if heading.squared_norm() == 0.0 {
+            return Step::new(self.as_collidable(), Vec2::zero());
+        }
+        Step::new(self.as_collidable(), heading.with_norm(step_size))
+    }
+
+    /**
+     * A step toward the position that stops on it rather than overshooting.
+     */
+    fn step_to(&self, position: &Vec2, step_size: f64) -> Step {
+        let delta = position - &self.position;
+        let distance = delta.norm();
+        if distance == 0.0 {
+            return Step::new(self.as_collidable(), Vec2::zero());
+        }
+        Step::new(
+            self.as_collidable(),
+            delta.with_norm(f64::min(step_size, distance)),
+        )
+    }
+
     pub fn as_collidable(&self) -> Collidable {
         Collidable::new(self.position, self.radius)
     }
 
     pub fn sprite(&self) -> &Sprite {
-        self.sprite_sheet
-            .get_sprite(&self.sprite_state, self.sprite_state_duration)
+        match &self.sprite_transition {
+            Some(from) => self.sprite_sheet.get_transition_sprite_at(
+                from,
+                &self.sprite_state,
+                self.sprite_state_elapsed,
+            ),
+            None => self
+                .sprite_sheet
+                .get_sprite_at(&self.sprite_state, self.sprite_state_elapsed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{ImageFormat, Rgba, RgbaImage};
+
+    use super::*;
+
+    /**
+     * One solid colour frame per shade, each showing for `millis`.
+     */
+    fn solid_animation(shades: &[u8], millis: u64) -> sprite::Animation {
+        let mut animation = sprite::Animation::new();
+        for shade in shades {
+            let image = RgbaImage::from_pixel(1, 1, Rgba([*shade, 0, 0, 255]));
+            animation.push(
+                Sprite::from_image(image.into(), ImageFormat::Png),
+                Duration::from_millis(millis),
+            );
+        }
+        animation
+    }
+
+    fn shade(creature: &Creature) -> u8 {
+        creature.sprite().as_image().to_rgba8().get_pixel(0, 0).0[0]
+    }
+
+    #[test]
+    fn test_take_step_transition() {
+        let mut sheet = sprite::Sheet::new();
+        sheet.set(&sprite::State::Idle, solid_animation(&[1], 1000));
+        sheet.set(&sprite::State::Sleep, solid_animation(&[3, 4], 1000));
+        sheet.set_transition(
+            &sprite::State::Idle,
+            &sprite::State::Sleep,
+            solid_animation(&[2], 1500),
+        );
+        sheet.derive_missing().unwrap();
+
+        let mut creature: Creature = Metadata::mock(0.1).into();
+        creature.sprite_sheet = Arc::new(sheet);
+        creature.creature_state = State::Sleep;
+        let standing_still = Step::new(creature.as_collidable(), Vec2::zero());
+        let tick = Duration::from_secs(1);
+        assert_eq!(shade(&creature), 1);
+
+        // falling asleep starts the transition from its first frame
+        creature.take_step(&standing_still, tick);
+        assert_eq!(creature.sprite_state, sprite::State::Sleep);
+        assert_eq!(creature.sprite_transition, Some(sprite::State::Idle));
+        assert_eq!(creature.sprite_state_elapsed, Duration::ZERO);
+        assert_eq!(shade(&creature), 2);
+
+        creature.take_step(&standing_still, tick);
+        assert_eq!(shade(&creature), 2);
+
+        // past the transition, sleep loops with whatever time is left over
+        creature.take_step(&standing_still, tick);
+        assert_eq!(creature.sprite_transition, None);
+        assert_eq!(creature.sprite_state_elapsed, Duration::from_millis(500));
+        assert_eq!(shade(&creature), 3);
+
+        creature.take_step(&standing_still, tick);
+        assert_eq!(shade(&creature), 4);
     }
 }
@@ -1,9 +1,10 @@
 use rand::{Rng, distr::weighted::WeightedIndex};
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+#[derive(Clone, PartialEq, Debug, Default, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum State {
+    #[default]
     Idle,
     Sleep,
     Follow,
@@ -41,9 +42,3 @@ impl State {
         }
     }
 }
-
-impl Default for State {
-    fn default() -> Self {
-        Self::Idle
-    }
-}
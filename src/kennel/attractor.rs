@@ -0,0 +1,88 @@
+use serde::Serialize;
+
+use crate::{kennel::Marker, math::Vec2};
+
+/**
+ * How many ticks an attractor stays put, if nobody says otherwise.
+ */
+pub static DEFAULT_ATTRACTOR_TICKS: usize = 20;
+
+/**
+ * How far from an attractor creatures still notice it, in kennel lengths.
+ * Its pull fades out linearly on the way there.
+ */
+pub static ATTRACTOR_RANGE: f64 = 0.3;
+
+/**
+ * How hard an attractor can pull or push, relative to the pull of the center of mass.
+ */
+pub static MAX_ATTRACTOR_STRENGTH: f64 = 4.0;
+
+/**
+ * A spot a visitor pointed at, like a thrown ball, that nearby following and fleeing creatures
+ * steer toward, or away from if its strength is negative.
+ */
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Attractor {
+    #[serde(flatten)]
+    pub marker: Marker,
+    pub strength: f64,
+}
+
+impl Attractor {
+    pub fn is_repeller(&self) -> bool {
+        self.strength < 0.0
+    }
+
+    /**
+     * Which way and how hard the attractor pulls a creature at `position`.
+     */
+    fn pull(&self, position: &Vec2) -> Vec2 {
+        let delta = &self.marker.position - position;
+        let distance = delta.norm();
+        if distance == 0.0 || distance >= ATTRACTOR_RANGE {
+            return Vec2::zero();
+        }
+        delta.with_norm(self.strength * (1.0 - distance / ATTRACTOR_RANGE))
+    }
+}
+
+/**
+ * The combined pull of all the attractors on a creature at `position`.
+ */
+pub fn pull(attractors: &[Attractor], position: &Vec2) -> Vec2 {
+    attractors.iter().fold(Vec2::zero(), |pull, attractor| {
+        pull + attractor.pull(position)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pull() {
+        let attractor = Attractor {
+            marker: Marker::new("Attractor", Vec2::new(0.5, 0.5), 1).unwrap(),
+            strength: 2.0,
+        };
+        let repeller = Attractor {
+            strength: -2.0,
+            ..attractor
+        };
+
+        // halfway out of range pulls with half the strength, toward the attractor
+        let pulled = pull(&[attractor], &Vec2::new(0.35, 0.5));
+        assert!((pulled.x - 1.0).abs() < 1e-9 && pulled.y.abs() < 1e-9);
+        let pushed = pull(&[repeller], &Vec2::new(0.35, 0.5));
+        assert!((pushed.x + 1.0).abs() < 1e-9);
+
+        // out of range, on top of it, or cancelled out does nothing
+        assert_eq!(pull(&[attractor], &Vec2::new(0.1, 0.5)), Vec2::zero());
+        assert_eq!(pull(&[attractor], &Vec2::new(0.5, 0.5)), Vec2::zero());
+        assert_eq!(
+            pull(&[attractor, repeller], &Vec2::new(0.4, 0.4)),
+            Vec2::zero()
+        );
+    }
+}
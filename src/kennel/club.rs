@@ -0,0 +1,551 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::time::Duration;
+
+use itertools::Itertools;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::creature::Creature;
+use crate::kennel::{Event, Kennel, free_position, load_metadata};
+use crate::math::Vec2;
+use crate::physics::{Collidable, Opening};
+use crate::render::Theme;
+
+/**
+ * The room creatures go in when their metadata doesn't name one.
+ */
+pub static DEFAULT_ROOM: &str = "kennel";
+
+/**
+ * How many ticks a creature that came through a doorway stays before it can take one again,
+ * so one fleeing out of a room doesn't flee right back.
+ */
+pub static DOORWAY_COOLDOWN_TICKS: usize = 10;
+
+/**
+ * A way between two rooms that creatures can be moved through.
+ * With `openings`, one in each room's walls in the order of `rooms`,
+ * creatures also wander through on their own.
+ */
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Door {
+    pub rooms: [String; 2],
+    #[serde(default)]
+    pub openings: Option<[Opening; 2]>,
+}
+
+/**
+ * An opening in a kennel's walls that leads to room `to`.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Doorway {
+    pub to: String,
+    pub opening: Opening,
+}
+
+/**
+ * A creature that wandered through a doorway during a tick.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Migration {
+    pub id: String,
+    pub from: String,
+    pub to: String,
+}
+
+impl Door {
+    pub fn connects(&self, room: &str, other: &str) -> bool {
+        let [first, second] = &self.rooms;
+        (first == room && second == other) || (first == other && second == room)
+    }
+
+    /**
+     * The doorway in `room`'s walls, if the door has openings and leads there.
+     */
+    fn doorway(&self, room: &str) -> Option<Doorway> {
+        let side = self.rooms.iter().position(|other| other == room)?;
+        self.openings.map(|openings| Doorway {
+            to: self.rooms[1 - side].clone(),
+            opening: openings[side],
+        })
+    }
+}
+
+/**
+ * A kennel for every room, each ticking on its own, and the doors between them.
+ * Rooms are kept in name order so a seeded rng ticks them the same way every time.
+ */
+#[derive(Clone, Default)]
+pub struct KennelClub {
+    rooms: BTreeMap<String, Kennel>,
+    doors: Vec<Door>,
+    migrations: Vec<Migration>,
+    /**
+     * Ticks left before creatures that just came through a doorway can take one again.
+     */
+    cooldowns: HashMap<String, usize>,
+}
+
+impl KennelClub {
+    pub fn new() -> Self {
+        KennelClub::default()
+    }
+
+    /**
+     * Loads every room in the data directory.
+     * Creatures in its metadata go in the room their `room` names, or `DEFAULT_ROOM`,
+     * and every subdirectory with metadata of its own is a room named after it.
+     * Doors between rooms are listed in `doors.json`.
+     */
+    pub fn load<R: Rng + ?Sized>(dir: &Path, rng: &mut R) -> Result<Self, String> {
+        let mut club = KennelClub::new();
+
+        if dir.join("metadata.json").exists() {
+            let theme = Theme::load(dir)?;
+            let by_room = load_metadata(dir)?
+                .into_iter()
+                .into_group_map_by(|metadata| {
+                    metadata
+                        .room
+                        .clone()
+                        .unwrap_or_else(|| DEFAULT_ROOM.to_string())
+                });
+            for (room, metadatas) in by_room.into_iter().sorted_by(|a, b| a.0.cmp(&b.0)) {
+                let creatures = metadatas
+                    .into_iter()
+                    .map(|metadata| Creature::load(metadata, dir))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let kennel = Kennel::new(creatures, rng)
+                    .map_err(|e| format!("Unable to set up room {}. {}", room, e))?
+                    .with_theme(theme.clone());
+                club.add_room(&room, kennel)?;
+            }
+        }
+
+        let subdirectories = std::fs::read_dir(dir)
+            .map_err(|e| format!("Unable to read {}. {}", dir.display(), e))?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.join("metadata.json").is_file())
+            .sorted();
+        for path in subdirectories {
+            let room = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .ok_or(format!("Unable to name the room in {}", path.display()))?;
+            let kennel = Kennel::load(&path, rng)
+                .map_err(|e| format!("Unable to load room {}. {}", room, e))?;
+            club.add_room(&room, kennel)?;
+        }
+
+        if club.rooms.is_empty() {
+            return Err(format!("No creatures or rooms in {}", dir.display()));
+        }
+
+        let doors_path = dir.join("doors.json");
+        if doors_path.exists() {
+            let json =
+                std::fs::read_to_string(&doors_path).map_err(|_| "Unable to read doors file")?;
+            let doors: Vec<Door> = serde_json::from_str(&json)
+                .map_err(|e| format!("Unable to deserialize doors. {}", e))?;
+            for door in doors.into_iter() {
+                club.add_door(door)?;
+            }
+        }
+
+        Ok(club)
+    }
+
+    /**
+     * Adds a room. Creature ids are unique across the whole club,
+     * so a creature can be found without knowing its room.
+     */
+    pub fn add_room(&mut self, name: &str, kennel: Kennel) -> Result<(), String> {
+        if name.is_empty() {
+            return Err("Room names can't be empty".to_string());
+        }
+        if self.rooms.contains_key(name) {
+            return Err(format!("Room {} is already in the club", name));
+        }
+        if let Some((room, creature)) = kennel
+            .creatures
+            .iter()
+            .find_map(|creature| self.find(&creature.id))
+        {
+            return Err(format!(
+                "Creature {} in room {} is already in room {}",
+                creature.id, name, room
+            ));
+        }
+
+        self.rooms.insert(name.to_string(), kennel);
+        Ok(())
+    }
+
+    /**
+     * Adds a door between two rooms already in the club.
+     */
+    pub fn add_door(&mut self, door: Door) -> Result<(), String> {
+        let [first, second] = &door.rooms;
+        if let Some(room) = door
+            .rooms
+            .iter()
+            .find(|room| !self.rooms.contains_key(*room))
+        {
+            return Err(format!(
+                "Door leads to room {}, which isn't in the club",
+                room
+            ));
+        }
+        if first == second {
+            return Err(format!("Door leads from room {} back into itself", first));
+        }
+        if self.doors.iter().any(|other| other.connects(first, second)) {
+            return Err(format!(
+                "Rooms {} and {} already have a door",
+                first, second
+            ));
+        }
+        for opening in door.openings.iter().flatten() {
+            opening.validate()?;
+        }
+
+        self.doors.push(door);
+        self.update_doorways()
+    }
+
+    /**
+     * Opens every room's walls where its doors are.
+     */
+    fn update_doorways(&mut self) -> Result<(), String> {
+        for (room, kennel) in self.rooms.iter_mut() {
+            let doorways = self
+                .doors
+                .iter()
+                .filter_map(|door| door.doorway(room))
+                .collect();
+            kennel.set_doorways(doorways)?;
+        }
+        Ok(())
+    }
+
+    pub fn room(&self, name: &str) -> Option<&Kennel> {
+        self.rooms.get(name)
+    }
+
+    pub fn room_mut(&mut self, name: &str) -> Option<&mut Kennel> {
+        self.rooms.get_mut(name)
+    }
+
+    /**
+     * Every room by name, in name order.
+     */
+    pub fn rooms(&self) -> impl Iterator<Item = (&str, &Kennel)> {
+        self.rooms
+            .iter()
+            .map(|(name, kennel)| (name.as_str(), kennel))
+    }
+
+    /**
+     * Takes a room out of the club, along with its doors.
+     */
+    pub fn remove_room(&mut self, name: &str) -> Result<Kennel, String> {
+        let kennel = self
+            .rooms
+            .remove(name)
+            .ok_or(format!("No room {} in the club", name))?;
+        self.doors
+            .retain(|door| door.rooms.iter().all(|room| room != name));
+        self.update_doorways()?;
+        Ok(kennel)
+    }
+
+    pub fn doors(&self) -> &[Door] {
+        &self.doors
+    }
+
+    /**
+     * The creature and the name of the room it's in.
+     */
+    pub fn find(&self, id: &str) -> Option<(&str, &Creature)> {
+        self.rooms()
+            .find_map(|(name, kennel)| kennel.get_creature(id).map(|creature| (name, creature)))
+    }
+
+    /**
+     * Sets how much time passes every tick in every room.
+     */
+    pub fn with_tick_duration(self, tick_duration: Duration) -> Self {
+        let rooms = self
+            .rooms
+            .into_iter()
+            .map(|(name, kennel)| (name, kennel.with_tick_duration(tick_duration)))
+            .collect();
+        KennelClub { rooms, ..self }
+    }
+
+    /**
+     * Moves every room forward a tick, then takes creatures that walked into a doorway
+     * to the matching doorway of the room it leads to, unless somebody's standing there.
+     * Returns who went where, which is also in `migrations` until the next tick.
+     * What else happened is in each room's `events`.
+     */
+    pub fn tick<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Result<&[Migration], String> {
+        for kennel in self.rooms.values_mut() {
+            kennel.tick(rng)?;
+        }
+
+        self.cooldowns.retain(|_, ticks| {
+            *ticks = ticks.saturating_sub(1);
+            *ticks > 0
+        });
+
+        let reached: Vec<Migration> = self
+            .rooms()
+            .flat_map(|(room, kennel)| {
+                kennel.events().iter().filter_map(move |event| match event {
+                    Event::DoorwayReached { id, to } => Some(Migration {
+                        id: id.clone(),
+                        from: room.to_string(),
+                        to: to.clone(),
+                    }),
+                    _ => None,
+                })
+            })
+            .collect();
+
+        self.migrations.clear();
+        for migration in reached.into_iter() {
+            if self.cooldowns.contains_key(&migration.id) {
+                continue;
+            }
+            let Some(position) = self.entrance(&migration) else {
+                continue;
+            };
+            self.move_creature(&migration.id, &migration.from, &migration.to, position)?;
+            self.cooldowns
+                .insert(migration.id.clone(), DOORWAY_COOLDOWN_TICKS);
+            self.migrations.push(migration);
+        }
+
+        Ok(&self.migrations)
+    }
+
+    /**
+     * The creatures that wandered through a doorway during the last tick.
+     */
+    pub fn migrations(&self) -> &[Migration] {
+        &self.migrations
+    }
+
+    /**
+     * Where the migrating creature comes out on the other side of the door,
+     * if nobody is in the way.
+     */
+    fn entrance(&self, migration: &Migration) -> Option<Vec2> {
+        let (_, creature) = self.find(&migration.id)?;
+        let destination = self.rooms.get(&migration.to)?;
+        let doorway = self
+            .doors
+            .iter()
+            .filter(|door| door.connects(&migration.from, &migration.to))
+            .find_map(|door| door.doorway(&migration.to))?;
+
+        let collidable =
+            Collidable::new(doorway.opening.entrance(creature.radius), creature.radius);
+        let is_blocked = destination
+            .creatures
+            .iter()
+            .any(|other| collidable.is_colliding(&other.as_collidable()));
+        (!is_blocked).then_some(collidable.position)
+    }
+
+    /**
+     * Walks a creature through the door to another room, where it's put somewhere free,
+     * and returns where. Whoever it was playing with stays behind.
+     */
+    pub fn transfer<R: Rng + ?Sized>(
+        &mut self,
+        id: &str,
+        to: &str,
+        rng: &mut R,
+    ) -> Result<Vec2, String> {
+        let (from, creature) = self
+            .find(id)
+            .ok_or(format!("No creature {} in the club", id))?;
+        let from = from.to_string();
+        if !self.doors.iter().any(|door| door.connects(&from, to)) {
+            return Err(format!("No door between rooms {} and {}", from, to));
+        }
+
+        let destination = self
+            .rooms
+            .get(to)
+            .ok_or(format!("No room {} in the club", to))?;
+        let position = free_position(&destination.creatures, creature, rng)?;
+
+        self.move_creature(id, &from, to, position)?;
+        Ok(position)
+    }
+
+    fn move_creature(
+        &mut self,
+        id: &str,
+        from: &str,
+        to: &str,
+        position: Vec2,
+    ) -> Result<(), String> {
+        if !self.rooms.contains_key(to) {
+            return Err(format!("No room {} in the club", to));
+        }
+        let mut creature = self
+            .rooms
+            .get_mut(from)
+            .ok_or(format!("No room {} in the club", from))?
+            .remove_creature(id)?;
+        creature.pairing = None;
+        self.rooms
+            .get_mut(to)
+            .expect("The room was just checked")
+            .insert_creature(creature, position);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, rngs::SmallRng};
+
+    use crate::{
+        creature::{self, Metadata},
+        kennel::Event,
+        physics::Wall,
+    };
+
+    use super::*;
+
+    static RNG_SEED: u64 = 1;
+
+    fn room(rng: &mut SmallRng) -> (Kennel, String) {
+        let creature: Creature = Metadata::mock(0.1).into();
+        let id = creature.id.clone();
+        (Kennel::new(vec![creature], rng).unwrap(), id)
+    }
+
+    fn door(room: &str, other: &str) -> Door {
+        Door {
+            rooms: [room.to_string(), other.to_string()],
+            openings: None,
+        }
+    }
+
+    #[test]
+    fn test_add_room() {
+        let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+        let (kennel, id) = room(&mut rng);
+        let mut club = KennelClub::new();
+
+        club.add_room("inside", kennel.clone()).unwrap();
+        assert!(
+            club.add_room("inside", Kennel::new(vec![], &mut rng).unwrap())
+                .is_err()
+        );
+        assert!(club.add_room("garden", kennel).is_err());
+        assert_eq!(club.find(&id).map(|(room, _)| room), Some("inside"));
+
+        assert!(club.add_door(door("inside", "garden")).is_err());
+        club.add_room("garden", Kennel::new(vec![], &mut rng).unwrap())
+            .unwrap();
+        club.add_door(door("garden", "inside")).unwrap();
+        assert!(club.add_door(door("inside", "garden")).is_err());
+        assert!(club.add_door(door("inside", "inside")).is_err());
+
+        club.remove_room("garden").unwrap();
+        assert!(club.doors().is_empty());
+    }
+
+    #[test]
+    fn test_transfer() {
+        let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+        let (inside, id) = room(&mut rng);
+        let (garden, _) = room(&mut rng);
+        let mut club = KennelClub::new();
+        club.add_room("inside", inside).unwrap();
+        club.add_room("garden", garden).unwrap();
+        club.add_room("attic", Kennel::new(vec![], &mut rng).unwrap())
+            .unwrap();
+        club.add_door(door("inside", "garden")).unwrap();
+
+        assert!(club.transfer(&id, "attic", &mut rng).is_err());
+        assert!(club.transfer("nobody", "garden", &mut rng).is_err());
+
+        club.transfer(&id, "garden", &mut rng).unwrap();
+        assert_eq!(club.find(&id).map(|(room, _)| room), Some("garden"));
+        assert!(club.room("inside").unwrap().creatures().is_empty());
+        assert_eq!(club.room("garden").unwrap().creatures().len(), 2);
+
+        // each room hears about its side of it on the next tick
+        club.tick(&mut rng).unwrap();
+        let removed = Event::CreatureRemoved { id: id.clone() };
+        let added = Event::CreatureAdded { id: id.clone() };
+        assert!(club.room("inside").unwrap().events().contains(&removed));
+        assert!(club.room("garden").unwrap().events().contains(&added));
+    }
+
+    #[test]
+    fn test_migrate() {
+        let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+        let mut metadata = Metadata::mock(0.1);
+        metadata.step_size = 0.5;
+        let id = metadata.id.clone();
+        let mut inside = Kennel::new(vec![metadata.into()], &mut rng).unwrap();
+        inside.creatures[0].position = Vec2::new(0.85, 0.5);
+        inside.force_state(&id, creature::State::Flee, 100).unwrap();
+
+        let mut club = KennelClub::new();
+        club.add_room("inside", inside).unwrap();
+        club.add_room("garden", Kennel::new(vec![], &mut rng).unwrap())
+            .unwrap();
+        let opening = |wall: Wall| Opening {
+            wall,
+            from: 0.3,
+            to: 0.7,
+        };
+        club.add_door(Door {
+            openings: Some([opening(Wall::East), opening(Wall::West)]),
+            ..door("inside", "garden")
+        })
+        .unwrap();
+        assert_eq!(club.room("garden").unwrap().doorways()[0].to, "inside");
+
+        // fleeing east, the creature goes through the door and comes out of the garden's west wall
+        let migrations = club.tick(&mut rng).unwrap().to_vec();
+        assert_eq!(
+            migrations,
+            vec![Migration {
+                id: id.clone(),
+                from: "inside".to_string(),
+                to: "garden".to_string(),
+            }]
+        );
+        let (room, creature) = club.find(&id).unwrap();
+        assert_eq!(room, "garden");
+        assert!(creature.position.x < 0.11);
+        let reached = Event::DoorwayReached {
+            id: id.clone(),
+            to: "garden".to_string(),
+        };
+        assert!(club.room("inside").unwrap().events().contains(&reached));
+
+        // fleeing west from the middle of the garden runs right back into the door,
+        // which it can't take again until it has been there a while
+        assert!(club.tick(&mut rng).unwrap().is_empty());
+        assert_eq!(club.find(&id).unwrap().0, "garden");
+        for _ in 1..DOORWAY_COOLDOWN_TICKS {
+            club.tick(&mut rng).unwrap();
+        }
+        assert_eq!(club.find(&id).unwrap().0, "inside");
+    }
+}
@@ -1,19 +1,16 @@
-use std::{
-    cmp::Ordering,
-    collections::{BinaryHeap, HashSet},
-};
+use std::{cmp::Ordering, collections::BinaryHeap};
 
-use crate::physics::Step;
+use crate::physics::{Bound, Opening, Step};
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 enum ArenaCollision {
-    Bound(usize, f64),
+    Bound(usize, f64, Bound),
     Steps((usize, usize), f64),
 }
 
 impl ArenaCollision {
-    fn new_bound_collision(idx: usize, time: f64) -> Self {
-        Self::Bound(idx, time)
+    fn new_bound_collision(idx: usize, time: f64, bound: Bound) -> Self {
+        Self::Bound(idx, time, bound)
     }
 
     fn new_steps_collision(indices: (usize, usize), time: f64) -> Self {
@@ -22,7 +19,7 @@ impl ArenaCollision {
 
     fn time(&self) -> f64 {
         match self {
-            ArenaCollision::Bound(_, time) => *time,
+            ArenaCollision::Bound(_, time, _) => *time,
             ArenaCollision::Steps(_, time) => *time,
         }
     }
@@ -42,89 +39,130 @@ impl Ord for ArenaCollision {
     }
 }
 
+/**
+ * A collision that stopped a step short, by the index of the steps involved.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hit {
+    Wall(usize),
+    /**
+     * A step that walked into an opening in the walls, by the opening's index.
+     */
+    Opening(usize, usize),
+    Steps(usize, usize),
+}
+
+/**
+ * Resolves a tick's steps against each other and the walls.
+ * Clearing it between ticks keeps its allocations around for the next one.
+ */
+#[derive(Clone, Default)]
 pub struct Arena {
     steps: Vec<Step>,
     heap: BinaryHeap<ArenaCollision>,
+    resolved: Vec<Step>,
+    hits: Vec<Hit>,
+    visited: Vec<bool>,
 }
 
 impl Arena {
     pub fn new() -> Self {
-        Arena {
-            steps: Vec::new(),
-            heap: BinaryHeap::new(),
-        }
+        Arena::default()
     }
 
-    pub fn add(&mut self, new_step: Step) {
+    pub fn clear(&mut self) {
+        self.steps.clear();
+        self.heap.clear();
+        self.resolved.clear();
+        self.hits.clear();
+        self.visited.clear();
+    }
+
+    /**
+     * Adds a step, which stops at the walls but goes through the `openings` in them.
+     */
+    pub fn add(&mut self, new_step: Step, openings: &[Opening]) {
         let new_idx = self.steps.len();
 
         // add in bound collisions
-        if let Some(time) = new_step.unit_bound_collision_time() {
-            let collision = ArenaCollision::new_bound_collision(new_idx, time);
+        if let Some((time, bound)) = new_step.unit_bound_collision_time(openings) {
+            let collision = ArenaCollision::new_bound_collision(new_idx, time, bound);
             self.heap.push(collision);
         }
 
         // add in step collisions
         self.steps
             .iter()
-            .filter_map(|step| Step::steps_collision_time(step, &new_step))
             .enumerate()
+            .filter_map(|(idx, step)| {
+                Step::steps_collision_time(step, &new_step).map(|time| (idx, time))
+            })
             .map(|(idx, time)| ArenaCollision::new_steps_collision((idx, new_idx), time))
             .for_each(|collision| self.heap.push(collision));
 
         self.steps.push(new_step);
     }
 
-    pub fn into_vec(self) -> Vec<Step> {
-        let mut visited_indices = HashSet::<usize>::new();
-        let mut vec: Vec<Step> = vec![Step::default(); self.steps.len()];
+    #[cfg(test)]
+    pub fn into_vec(mut self) -> Vec<Step> {
+        self.resolve().0.to_vec()
+    }
 
-        // resolve all collisions
-        for collision in self.heap.into_iter() {
+    /**
+     * Resolves the steps, stopping each at its first collision,
+     * and returns them in the order they were added along with the hits that stopped them.
+     * Hits refer to steps by the order they were added.
+     */
+    pub fn resolve(&mut self) -> (&[Step], &[Hit]) {
+        self.resolved.clone_from(&self.steps);
+        self.visited.clear();
+        self.visited.resize(self.steps.len(), false);
+        self.hits.clear();
+
+        // resolve all collisions, earliest first
+        while let Some(collision) = self.heap.pop() {
             if collision.time() >= 1.0 {
                 break;
             }
 
             // if some step in this collision already got resolved, skip it
             match collision {
-                ArenaCollision::Bound(idx, _) if visited_indices.contains(&idx) => continue,
-                ArenaCollision::Steps((idx, _), _) if visited_indices.contains(&idx) => continue,
-                ArenaCollision::Steps((_, idx), _) if visited_indices.contains(&idx) => continue,
+                ArenaCollision::Bound(idx, _, _) if self.visited[idx] => continue,
+                ArenaCollision::Steps((idx, _), _) if self.visited[idx] => continue,
+                ArenaCollision::Steps((_, idx), _) if self.visited[idx] => continue,
                 _ => (),
             }
 
-            // add the collision into the final vec
+            // stop the step short where it collided
             let mut add_collision = |idx: usize, time: f64| {
-                let lerped_step = self
+                self.resolved[idx] = self
                     .steps
                     .get(idx)
                     .expect("Unable to retrieve step from collision arena")
                     .lerp(time);
-                vec[idx] = lerped_step;
-                visited_indices.insert(idx);
+                self.visited[idx] = true;
             };
 
             match collision {
-                ArenaCollision::Bound(idx, time) => add_collision(idx, time),
+                ArenaCollision::Bound(idx, time, bound) => {
+                    add_collision(idx, time);
+                    self.hits.push(match bound {
+                        Bound::Wall => Hit::Wall(idx),
+                        Bound::Opening(opening) => Hit::Opening(idx, opening),
+                    });
+                }
                 ArenaCollision::Steps((idx1, idx2), time) => {
                     add_collision(idx1, time);
                     add_collision(idx2, time);
+                    self.hits.push(Hit::Steps(idx1, idx2));
                 }
             }
         }
 
-        // fill in the rest of steps that did not collide
-        for (idx, vec_step) in vec.iter_mut().enumerate() {
-            if visited_indices.contains(&idx as &usize) {
-                continue;
-            }
-
-            if let Some(step) = self.steps.get(idx) {
-                *vec_step = step.clone();
-            }
-        }
+        // whatever is left in the heap happens after this tick
+        self.heap.clear();
 
-        vec
+        (&self.resolved, &self.hits)
     }
 }
 
@@ -132,7 +170,7 @@ impl Arena {
 mod tests {
     use crate::{
         math::Vec2,
-        physics::{Collidable, DISTANCE_TOLERANCE},
+        physics::{Collidable, DISTANCE_TOLERANCE, Wall},
     };
 
     use super::*;
@@ -143,12 +181,12 @@ mod tests {
         let step = Step::new(collidable, Vec2::new(0.25, 0.0));
 
         let mut arena = Arena::new();
-        arena.add(step);
+        arena.add(step, &[]);
 
         let vec = arena.into_vec();
         let expected_position = Vec2::new(0.75, 0.5);
         let actual_position = vec
-            .get(0)
+            .first()
             .expect("Arena must contain step")
             .resolve()
             .position;
@@ -169,18 +207,18 @@ mod tests {
         let upper_bound = 1.0 - radius - DISTANCE_TOLERANCE;
 
         let collidable1 = Collidable::new(Vec2::new(lower_bound, lower_bound), radius);
-        let step1 = Step::new(collidable1, delta.clone());
+        let step1 = Step::new(collidable1, delta);
 
         let collidable2 = Collidable::new(Vec2::new(upper_bound, upper_bound), radius);
-        let step2 = Step::new(collidable2, -delta.clone());
+        let step2 = Step::new(collidable2, -delta);
 
         let mut arena = Arena::new();
-        arena.add(step1);
-        arena.add(step2);
+        arena.add(step1, &[]);
+        arena.add(step2, &[]);
         let vec = arena.into_vec();
 
         let resolved_collidable1 = vec
-            .get(0)
+            .first()
             .expect("Arena did not produce enough steps")
             .resolve();
 
@@ -196,6 +234,80 @@ mod tests {
         assert!(distance < 2.0 * (radius + DISTANCE_TOLERANCE));
     }
 
+    #[test]
+    fn test_collisions() {
+        // the first step hits nothing, the middle two run into each other and the last into a wall
+        let idle = Step::new(Collidable::new(Vec2::new(0.2, 0.2), 0.1), Vec2::zero());
+        let left = Step::new(
+            Collidable::new(Vec2::new(0.3, 0.8), 0.1),
+            Vec2::new(0.3, 0.0),
+        );
+        let right = Step::new(
+            Collidable::new(Vec2::new(0.7, 0.8), 0.1),
+            Vec2::new(-0.3, 0.0),
+        );
+        let wall = Step::new(
+            Collidable::new(Vec2::new(0.8, 0.3), 0.1),
+            Vec2::new(0.3, 0.0),
+        );
+
+        let mut arena = Arena::new();
+        [idle, left, right, wall.clone()]
+            .into_iter()
+            .for_each(|step| arena.add(step, &[]));
+        let mut hits = arena.resolve().1.to_vec();
+        hits.sort_by_key(|hit| format!("{:?}", hit));
+
+        assert_eq!(hits, vec![Hit::Steps(1, 2), Hit::Wall(3)]);
+
+        // the step into the wall goes into an opening in it instead, but not one that's too narrow
+        let openings = [
+            Opening {
+                wall: Wall::East,
+                from: 0.25,
+                to: 0.3,
+            },
+            Opening {
+                wall: Wall::East,
+                from: 0.1,
+                to: 0.5,
+            },
+        ];
+        arena.clear();
+        arena.add(wall, &openings);
+        assert_eq!(arena.resolve().1, &[Hit::Opening(0, 1)]);
+    }
+
+    #[test]
+    fn test_resolve_earliest_first() {
+        // a step running into the north wall right away, so the step pairs that come later
+        // aren't attributed to it and the heap is out of time order when iterated as stored
+        let early = Step::new(
+            Collidable::new(Vec2::new(0.5, 0.15), 0.1),
+            Vec2::new(0.0, -0.5),
+        );
+        // runs into the east wall near the end of its step, but into `crossing` well before
+        let late = Step::new(
+            Collidable::new(Vec2::new(0.5, 0.5), 0.1),
+            Vec2::new(0.45, 0.0),
+        );
+        let crossing = Step::new(
+            Collidable::new(Vec2::new(0.85, 0.75), 0.1),
+            Vec2::new(0.0, -0.5),
+        );
+
+        let mut arena = Arena::new();
+        [early, late, crossing]
+            .into_iter()
+            .for_each(|step| arena.add(step, &[]));
+        let resolved: Vec<_> = arena.into_vec().iter().map(Step::resolve).collect();
+
+        assert!(resolved[0].position.y < 0.11);
+        assert!(resolved[1].position.x < 0.7);
+        assert!(resolved[2].position.y > 0.5);
+        assert!(!resolved[1].is_colliding(&resolved[2]));
+    }
+
     #[test]
     fn test_step_collision_tweener() {
         let radius = 0.1;
@@ -208,22 +320,22 @@ mod tests {
         let upper_bound = 1.0 - radius - DISTANCE_TOLERANCE;
 
         let collidable1 = Collidable::new(Vec2::new(lower_bound, lower_bound), radius);
-        let step1 = Step::new(collidable1, delta.clone());
+        let step1 = Step::new(collidable1, delta);
 
         let collidable2 = Collidable::new(Vec2::new(upper_bound, upper_bound), radius);
-        let step2 = Step::new(collidable2, -delta.clone());
+        let step2 = Step::new(collidable2, -delta);
 
         let stationary_collidable = Collidable::new(Vec2::new(0.5, 0.5), radius);
         let stationary_step = Step::new(stationary_collidable, Vec2::new(0.0, 0.0));
 
         let mut arena = Arena::new();
-        arena.add(step1);
-        arena.add(step2);
-        arena.add(stationary_step);
+        arena.add(step1, &[]);
+        arena.add(step2, &[]);
+        arena.add(stationary_step, &[]);
         let vec = arena.into_vec();
 
         let resolved_collidable1 = vec
-            .get(0)
+            .first()
             .expect("Arena did not produce enough steps")
             .resolve();
 
@@ -0,0 +1,78 @@
+use serde::Serialize;
+
+use crate::creature::{self, Interaction};
+
+/**
+ * Something that happened in the kennel, so nobody has to diff snapshots to notice.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Event {
+    StateChanged {
+        id: String,
+        from: creature::State,
+        to: creature::State,
+    },
+    Collision {
+        ids: [String; 2],
+    },
+    WallHit {
+        id: String,
+    },
+    DoorwayReached {
+        id: String,
+        to: String,
+    },
+    CreatureAdded {
+        id: String,
+    },
+    CreatureRemoved {
+        id: String,
+    },
+    InteractionStarted {
+        interaction: Interaction,
+        ids: [String; 2],
+    },
+    InteractionEnded {
+        interaction: Interaction,
+        ids: [String; 2],
+    },
+}
+
+impl Event {
+    /**
+     * Whether the creature had a part in it.
+     */
+    pub fn involves(&self, id: &str) -> bool {
+        match self {
+            Event::StateChanged { id: other, .. }
+            | Event::WallHit { id: other }
+            | Event::DoorwayReached { id: other, .. }
+            | Event::CreatureAdded { id: other }
+            | Event::CreatureRemoved { id: other } => other == id,
+            Event::Collision { ids }
+            | Event::InteractionStarted { ids, .. }
+            | Event::InteractionEnded { ids, .. } => ids.iter().any(|other| other == id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize() {
+        let event = Event::StateChanged {
+            id: "alt".to_string(),
+            from: creature::State::Idle,
+            to: creature::State::Sleep,
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"kind":"state_changed","id":"alt","from":"idle","to":"sleep"}"#
+        );
+        assert!(event.involves("alt"));
+        assert!(!event.involves("someone else"));
+    }
+}
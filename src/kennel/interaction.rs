@@ -0,0 +1,220 @@
+use rand::Rng;
+use serde::Serialize;
+
+use crate::creature::{Creature, Interaction, Pairing, State};
+
+/**
+ * When two creatures start an interaction.
+ * The leader is in one of the `leader` states and its partner in one of the `partner` states,
+ * with at most `range` between their edges.
+ */
+struct Rule {
+    interaction: Interaction,
+    leader: &'static [State],
+    partner: &'static [State],
+    range: f64,
+    chance: f64,
+    ticks: usize,
+}
+
+/**
+ * Checked in order, the first rule that applies to a pair is the only one rolled for.
+ */
+static RULES: [Rule; 3] = [
+    // a sleeper pulls whoever's lazing nearby into a nap
+    Rule {
+        interaction: Interaction::Nap,
+        leader: &[State::Sleep],
+        partner: &[State::Sleep, State::Idle],
+        range: 0.03,
+        chance: 0.3,
+        ticks: 20,
+    },
+    // running into someone running away turns into a game
+    Rule {
+        interaction: Interaction::Chase,
+        leader: &[State::Follow],
+        partner: &[State::Flee],
+        range: 0.05,
+        chance: 0.25,
+        ticks: 8,
+    },
+    Rule {
+        interaction: Interaction::Sniff,
+        leader: &[State::Idle, State::Follow],
+        partner: &[State::Idle, State::Follow],
+        range: 0.02,
+        chance: 0.5,
+        ticks: 3,
+    },
+];
+
+impl Rule {
+    fn applies(&self, leader: &Creature, partner: &Creature) -> bool {
+        let gap = (&leader.position - &partner.position).norm() - leader.radius - partner.radius;
+        gap <= self.range
+            && self.leader.contains(&leader.creature_state)
+            && self.partner.contains(&partner.creature_state)
+    }
+}
+
+/**
+ * An interaction going on between two creatures, leader first.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InteractionEvent {
+    pub interaction: Interaction,
+    pub ids: [String; 2],
+    pub ticks_left: usize,
+}
+
+/**
+ * Every interaction going on, once per pair, leader first.
+ * A pair shows up even if one side already left, until the other side notices.
+ */
+pub fn events(creatures: &[Creature]) -> Vec<InteractionEvent> {
+    let mut events: Vec<InteractionEvent> = vec![];
+    for creature in creatures.iter() {
+        let Some(pairing) = &creature.pairing else {
+            continue;
+        };
+
+        let (id, partner) = (creature.id.clone(), pairing.partner.clone());
+        let ids = if pairing.leads {
+            [id, partner]
+        } else {
+            [partner, id]
+        };
+        if !events.iter().any(|event| event.ids == ids) {
+            events.push(InteractionEvent {
+                interaction: pairing.interaction,
+                ids,
+                ticks_left: pairing.ticks_left,
+            });
+        }
+    }
+    events
+}
+
+/**
+ * Ends the side of any interaction whose partner isn't taking part anymore,
+ * e.g. because it left the kennel or was forced into something else.
+ */
+pub fn end_abandoned(creatures: &mut [Creature]) {
+    for idx in 0..creatures.len() {
+        let Some(pairing) = &creatures[idx].pairing else {
+            continue;
+        };
+
+        let id = &creatures[idx].id;
+        let is_abandoned = !creatures.iter().any(|other| {
+            other.id == pairing.partner
+                && other
+                    .pairing
+                    .as_ref()
+                    .is_some_and(|other_pairing| &other_pairing.partner == id)
+        });
+        if is_abandoned {
+            creatures[idx].pairing = None;
+        }
+    }
+}
+
+/**
+ * Rolls for new interactions between creatures that aren't already busy with one.
+ */
+pub fn start<R: Rng + ?Sized>(creatures: &mut [Creature], rng: &mut R) {
+    for idx1 in 0..creatures.len() {
+        for idx2 in (idx1 + 1)..creatures.len() {
+            let (head, tail) = creatures.split_at_mut(idx2);
+            let (creature1, creature2) = (&mut head[idx1], &mut tail[0]);
+            let is_busy = |creature: &Creature| {
+                creature.pairing.is_some()
+                    || creature.forced_state.is_some()
+                    || !creature.commands.is_empty()
+            };
+            if is_busy(creature1) || is_busy(creature2) {
+                continue;
+            }
+
+            let rule = RULES.iter().find_map(|rule| {
+                if rule.applies(creature1, creature2) {
+                    Some((rule, true))
+                } else if rule.applies(creature2, creature1) {
+                    Some((rule, false))
+                } else {
+                    None
+                }
+            });
+            let Some((rule, first_leads)) = rule else {
+                continue;
+            };
+            if !rng.random_bool(rule.chance) {
+                continue;
+            }
+
+            let pairing = |partner: &Creature, leads: bool| Pairing {
+                interaction: rule.interaction,
+                partner: partner.id.clone(),
+                leads,
+                ticks_left: rule.ticks,
+            };
+            let (pairing1, pairing2) = (
+                pairing(creature2, first_leads),
+                pairing(creature1, !first_leads),
+            );
+            creature1.pairing = Some(pairing1);
+            creature2.pairing = Some(pairing2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, rngs::SmallRng};
+
+    use super::*;
+    use crate::{creature::Metadata, math::Vec2};
+
+    fn creature(x: f64, state: State) -> Creature {
+        let creature: Creature = Metadata::mock(0.05).into();
+        Creature {
+            creature_state: state,
+            ..creature.set_position(Vec2::new(x, 0.5))
+        }
+    }
+
+    #[test]
+    fn test_start() {
+        let mut rng = SmallRng::seed_from_u64(1);
+
+        // the follower leads the chase even though it's second, the far one is left alone
+        let mut creatures = vec![
+            creature(0.2, State::Flee),
+            creature(0.31, State::Follow),
+            creature(0.9, State::Idle),
+        ];
+        while creatures[0].pairing.is_none() {
+            start(&mut creatures, &mut rng);
+        }
+
+        let pairing = creatures[0].pairing.as_ref().unwrap();
+        assert_eq!(pairing.interaction, Interaction::Chase);
+        assert_eq!(pairing.partner, creatures[1].id);
+        assert!(!pairing.leads);
+        assert!(creatures[1].pairing.as_ref().unwrap().leads);
+        assert!(creatures[2].pairing.is_none());
+
+        let events = events(&creatures);
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            events[0].ids,
+            [creatures[1].id.clone(), creatures[0].id.clone()]
+        );
+
+        // leaving ends the interaction for the one left behind
+        creatures.remove(1);
+        end_abandoned(&mut creatures);
+        assert!(creatures[0].pairing.is_none());
+    }
+}
@@ -0,0 +1,58 @@
+use serde::Serialize;
+
+use crate::math::Vec2;
+
+/**
+ * A spot in the kennel that wears off after a number of ticks,
+ * like a dropped treat or a thrown ball.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Marker {
+    pub position: Vec2,
+    pub ticks_left: usize,
+}
+
+impl Marker {
+    /**
+     * A marker at `position` for the next `ticks` ticks, named `what` in errors.
+     */
+    pub fn new(what: &str, position: Vec2, ticks: usize) -> Result<Self, String> {
+        if !(0.0..=1.0).contains(&position.x) || !(0.0..=1.0).contains(&position.y) {
+            return Err(format!(
+                "{} at ({}, {}) is outside the kennel",
+                what, position.x, position.y
+            ));
+        }
+        if ticks == 0 {
+            return Err(format!("{} must last at least 1 tick", what));
+        }
+
+        Ok(Marker {
+            position,
+            ticks_left: ticks,
+        })
+    }
+
+    /**
+     * Counts down a tick, returning whether the marker is still around after it.
+     */
+    pub fn wear_off(&mut self) -> bool {
+        self.ticks_left = self.ticks_left.saturating_sub(1);
+        self.ticks_left > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        let mut marker = Marker::new("Treat", Vec2::new(0.5, 0.5), 2).unwrap();
+        assert!(marker.wear_off());
+        assert!(!marker.wear_off());
+
+        assert!(Marker::new("Treat", Vec2::new(1.5, 0.5), 2).is_err());
+        assert!(Marker::new("Treat", Vec2::new(0.5, 0.5), 0).is_err());
+    }
+}
@@ -1,92 +1,279 @@
-use std::io::{BufWriter, Cursor};
+use std::io::Write;
 use std::iter::zip;
 use std::path::Path;
+use std::time::Duration;
 
-use image::imageops::overlay;
-use image::{DynamicImage, ImageFormat, RgbaImage};
+use image::ImageFormat;
 use itertools::Itertools;
 use rand::Rng;
+use serde::Serialize;
 use termion::terminal_size;
 
+use crate::auth::Owners;
 use crate::creature::{self, Creature};
-use crate::kennel::collision::Arena;
+use crate::kennel::collision::{Arena, Hit};
 use crate::math::Vec2;
-use crate::physics::Collidable;
+use crate::physics::{Collidable, Opening};
+use crate::render::{self, HitRegion, ImageOptions, Scene, Terminal, Theme};
 use crate::{Sprite, sprite};
 
+pub use attractor::{ATTRACTOR_RANGE, Attractor, DEFAULT_ATTRACTOR_TICKS, MAX_ATTRACTOR_STRENGTH};
+pub use club::{DEFAULT_ROOM, DOORWAY_COOLDOWN_TICKS, Door, Doorway, KennelClub, Migration};
+pub use event::Event;
+pub use interaction::InteractionEvent;
+pub use marker::Marker;
+pub use simulation::{DEFAULT_MAX_SUBSTEPS, Simulation};
+pub use snapshot::{CreatureSnapshot, Snapshot};
+pub use stats::{CreatureStats, DEFAULT_HEATMAP_RESOLUTION, Heatmap, StateTicks, Stats};
+pub use treat::{DEFAULT_TREAT_TICKS, Treat};
+
+mod attractor;
+mod club;
 mod collision;
+mod event;
+mod interaction;
+mod marker;
+mod simulation;
+mod snapshot;
+mod stats;
+mod treat;
 
+#[derive(Clone)]
 pub struct Kennel {
     creatures: Vec<Creature>,
+    tick_duration: Duration,
+    theme: Theme,
+    treats: Vec<Treat>,
+    attractors: Vec<Attractor>,
+    doorways: Vec<Doorway>,
+    /**
+     * The doorways' openings, for the arena.
+     */
+    openings: Vec<Opening>,
+    events: Vec<Event>,
+    /**
+     * What happened since the last tick, e.g. creatures that were added, to be told about with it.
+     */
+    pending_events: Vec<Event>,
+    arena: Arena,
+}
+
+/**
+ * What a reload changed, by creature id.
+ */
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct Reload {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub updated: Vec<String>,
 }
 
 static MAX_INITIALIZATION_RETRIES: u8 = 32;
 
-impl Kennel {
-    pub fn load<R: Rng + ?Sized>(dir: &Path, rng: &mut R) -> Result<Self, String> {
-        let json = std::fs::read_to_string(dir.join("metadata.json"))
-            .map_err(|_| "Unable to read metadata file")?;
+/**
+ * How many commands a creature holds on to at once, so its owner can't line up hours of them.
+ */
+pub static MAX_QUEUED_COMMANDS: usize = 4;
 
-        let metadatas: Vec<creature::Metadata> = serde_json::from_str(&json)
-            .map_err(|e| format!("Unable to deserialize creature metadata. {}", e))?;
+fn load_metadata(dir: &Path) -> Result<Vec<creature::Metadata>, String> {
+    let json = std::fs::read_to_string(dir.join("metadata.json"))
+        .map_err(|_| "Unable to read metadata file")?;
 
-        let creatures: Vec<Creature> = metadatas
-            .into_iter()
-            .map(|metadata| Creature::load(metadata, dir))
-            .collect();
+    serde_json::from_str(&json)
+        .map_err(|e| format!("Unable to deserialize creature metadata. {}", e))
+}
+
+/**
+ * Loads the creatures of a single room.
+ * Metadata that puts creatures in rooms is for a `KennelClub` to load,
+ * rather than quietly lumping every room together.
+ */
+fn load_creatures(dir: &Path) -> Result<Vec<Creature>, String> {
+    let metadatas = load_metadata(dir)?;
+    if let Some(metadata) = metadatas.iter().find(|metadata| metadata.room.is_some()) {
+        return Err(format!(
+            "Creature {} is in room {}, load {} as a kennel club instead",
+            metadata.id,
+            metadata.room.as_deref().unwrap_or_default(),
+            dir.display()
+        ));
+    }
+
+    metadatas
+        .into_iter()
+        .map(|metadata| Creature::load(metadata, dir))
+        .collect()
+}
+
+/**
+ * Finds a spot for the creature where it isn't colliding with any of the others or the walls.
+ * I know dart throwing is not sexy, but give me a break, this is like. n=10 or something.
+ */
+fn free_position<R: Rng + ?Sized>(
+    creatures: &[Creature],
+    creature: &Creature,
+    rng: &mut R,
+) -> Result<Vec2, String> {
+    let radius = creature.radius;
+    let diameter = radius * 2.0;
+    if diameter > 1.0 {
+        return Err(format!(
+            "Creature {} has radius {} and is too large for the kennel size.",
+            creature.id, radius
+        ));
+    }
 
-        Kennel::new(creatures, rng)
+    let random_collidable = |_| {
+        let position = Vec2::new(
+            rng.random_range(f64::next_up(radius)..(1.0 - radius)),
+            rng.random_range(f64::next_up(radius)..(1.0 - radius)),
+        );
+        Collidable::new(position, radius)
+    };
+
+    let is_not_colliding = |collidable: &Collidable| {
+        !creatures
+            .iter()
+            .any(|other| collidable.is_colliding(&other.as_collidable()))
+    };
+
+    (0..MAX_INITIALIZATION_RETRIES)
+        .map(random_collidable)
+        .find(is_not_colliding)
+        .map(|collidable| collidable.position)
+        .ok_or(format!("Unable to position creature {}", creature.id))
+}
+
+/**
+ * How much time a call to `next` represents.
+ * Creatures walk their `step_size` over a second, and sprite animations play in time.
+ */
+pub static DEFAULT_TICK_DURATION: Duration = Duration::from_secs(1);
+
+impl Kennel {
+    pub fn load<R: Rng + ?Sized>(dir: &Path, rng: &mut R) -> Result<Self, String> {
+        let creatures = load_creatures(dir)?;
+        let theme = Theme::load(dir)?;
+
+        Ok(Kennel::new(creatures, rng)?.with_theme(theme))
     }
 
     /**
      * Initialize a new kennel and reposition creatures such that no two are colliding
      * and none are colliding within the walls.
-     * I know dart throwing is not sexy, but give me a break, this is like. n=10 or something.
      */
     pub fn new<R: Rng + ?Sized>(creatures: Vec<Creature>, rng: &mut R) -> Result<Self, String> {
         let mut repositioned_creatures: Vec<Creature> = vec![];
-        for current_creature in creatures.into_iter() {
-            let radius = current_creature.radius;
-            let diameter = radius * 2.0;
-            if diameter > 1.0 {
-                return Err(format!(
-                    "Creature {} has radius {} and is too large for the kennel size.",
-                    current_creature.id, radius
-                ));
-            }
+        for creature in creatures.into_iter() {
+            let position = free_position(&repositioned_creatures, &creature, rng)?;
+            repositioned_creatures.push(creature.set_position(position));
+        }
+
+        Ok(Kennel {
+            creatures: repositioned_creatures,
+            tick_duration: DEFAULT_TICK_DURATION,
+            theme: Theme::default(),
+            treats: vec![],
+            attractors: vec![],
+            doorways: vec![],
+            openings: vec![],
+            events: vec![],
+            pending_events: vec![],
+            arena: Arena::new(),
+        })
+    }
+
+    /**
+     * Picks up changes to the data directory without starting over.
+     * New creatures are placed somewhere free, removed ones leave,
+     * and the rest take their new metadata and sprites where they stand.
+     * If anything fails to load, the kennel is left as it was.
+     */
+    pub fn reload<R: Rng + ?Sized>(&mut self, dir: &Path, rng: &mut R) -> Result<Reload, String> {
+        let creatures = load_creatures(dir)?;
+        let theme = Theme::load(dir)?;
+
+        let reload = self.replace_creatures(creatures, rng)?;
+        self.theme = theme;
+
+        let added = reload
+            .added
+            .iter()
+            .map(|id| Event::CreatureAdded { id: id.clone() });
+        let removed = (reload.removed.iter()).map(|id| Event::CreatureRemoved { id: id.clone() });
+        self.pending_events.extend(added.chain(removed));
+        Ok(reload)
+    }
 
-            let random_collidable = |_| {
-                let position = Vec2::new(
-                    rng.random_range(f64::next_up(radius)..(1.0 - radius)),
-                    rng.random_range(f64::next_up(radius)..(1.0 - radius)),
-                );
-                Collidable::new(position, radius)
+    fn replace_creatures<R: Rng + ?Sized>(
+        &mut self,
+        creatures: Vec<Creature>,
+        rng: &mut R,
+    ) -> Result<Reload, String> {
+        let mut reload = Reload::default();
+        let mut placed: Vec<Creature> = vec![];
+        let mut unplaced: Vec<Creature> = vec![];
+        for loaded in creatures.into_iter() {
+            let Some(current) = self.creatures.iter().find(|c| c.id == loaded.id) else {
+                reload.added.push(loaded.id.clone());
+                unplaced.push(loaded);
+                continue;
             };
 
-            let is_not_colliding = |collidable: &Collidable| {
-                !repositioned_creatures
+            // a creature that grew might not fit where it stands anymore
+            reload.updated.push(loaded.id.clone());
+            let creature = current.clone().with_metadata_of(loaded);
+            let collidable = creature.as_collidable();
+            let fits = !collidable.is_out_of_unit_bounds()
+                && !placed
                     .iter()
-                    .any(|creature| collidable.is_colliding(&creature.as_collidable()))
-            };
+                    .any(|other| collidable.is_colliding(&other.as_collidable()));
+            match fits {
+                true => placed.push(creature),
+                false => unplaced.push(creature),
+            }
+        }
+
+        for creature in unplaced.into_iter() {
+            let position = free_position(&placed, &creature, rng)?;
+            placed.push(creature.set_position(position));
+        }
 
-            let repositioned_collidable = (0..MAX_INITIALIZATION_RETRIES)
-                .map(random_collidable)
-                .find(is_not_colliding);
+        reload.removed = self
+            .creatures
+            .iter()
+            .filter(|creature| !placed.iter().any(|other| other.id == creature.id))
+            .map(|creature| creature.id.clone())
+            .collect();
+        self.creatures = placed;
+        Ok(reload)
+    }
 
-            match repositioned_collidable {
-                Some(c) => repositioned_creatures.push(current_creature.set_position(c.position)),
-                None => {
-                    return Err(format!(
-                        "Unable to position creature {}",
-                        current_creature.id
-                    ));
-                }
-            }
+    /**
+     * Sets how much time passes every call to `next`,
+     * which decides how far creatures walk and how far sprite animations play.
+     * Everything else that wears off, like states, commands and treats, counts ticks.
+     */
+    pub fn with_tick_duration(self, tick_duration: Duration) -> Self {
+        Kennel {
+            tick_duration,
+            ..self
         }
+    }
 
-        Ok(Kennel {
-            creatures: repositioned_creatures,
-        })
+    /**
+     * Sets how the kennel looks in rendered images.
+     */
+    pub fn with_theme(self, theme: Theme) -> Self {
+        Kennel { theme, ..self }
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    pub fn tick_duration(&self) -> Duration {
+        self.tick_duration
     }
 
     fn center_of_mass(&self) -> Vec2 {
@@ -115,33 +302,344 @@ impl Kennel {
      * creates a kennel that is in the next time step.
      * This moves each creature forward a time step
      * and de-collides them.
+     * Also returns what happened on the way, which is in the new kennel's `events` too.
      */
-    pub fn next<R: Rng + ?Sized>(&self, rng: &mut R) -> Result<Self, String> {
+    pub fn next<R: Rng + ?Sized>(&self, rng: &mut R) -> Result<(Self, Vec<Event>), String> {
+        let mut kennel = self.clone();
+        let events = kennel.tick(rng)?.to_vec();
+        Ok((kennel, events))
+    }
+
+    /**
+     * Same as `next`, but moves the kennel forward in place,
+     * reusing what it allocated on earlier ticks.
+     * Returns what happened, which is also in `events` until the next tick.
+     */
+    pub fn tick<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Result<&[Event], String> {
         let center_of_mass = self.center_of_mass();
-        let new_creatures: Vec<_> = self
+        self.events.clear();
+        self.events.append(&mut self.pending_events);
+
+        let interactions = interaction::events(&self.creatures);
+        for creature in self.creatures.iter_mut() {
+            let from = creature.creature_state.clone();
+            creature.advance_state(rng);
+            if creature.creature_state != from {
+                self.events.push(Event::StateChanged {
+                    id: creature.id.clone(),
+                    from,
+                    to: creature.creature_state.clone(),
+                });
+            }
+        }
+        interaction::end_abandoned(&mut self.creatures);
+
+        // creatures playing chase run after or away from each other instead of the rest
+        self.arena.clear();
+        for creature in self.creatures.iter() {
+            let playmate = creature
+                .pairing
+                .as_ref()
+                .filter(|pairing| pairing.interaction == creature::Interaction::Chase)
+                .and_then(|pairing| self.get_creature(&pairing.partner));
+            let step = match playmate {
+                Some(playmate) => creature.get_next_step(
+                    &playmate.position,
+                    None,
+                    &Vec2::zero(),
+                    self.tick_duration,
+                ),
+                None => {
+                    let treat = treat::nearest(&self.treats, &creature.position);
+                    let pull = attractor::pull(&self.attractors, &creature.position);
+                    creature.get_next_step(&center_of_mass, treat, &pull, self.tick_duration)
+                }
+            };
+            self.arena.add(step, &self.openings);
+        }
+
+        let (steps, hits) = self.arena.resolve();
+        let id = |idx: usize| self.creatures[idx].id.clone();
+        self.events.extend(hits.iter().map(|hit| match *hit {
+            Hit::Wall(idx) => Event::WallHit { id: id(idx) },
+            Hit::Opening(idx, opening) => Event::DoorwayReached {
+                id: id(idx),
+                to: self.doorways[opening].to.clone(),
+            },
+            Hit::Steps(idx1, idx2) => Event::Collision {
+                ids: [id(idx1), id(idx2)],
+            },
+        }));
+
+        for (creature, step) in zip(self.creatures.iter_mut(), steps) {
+            creature.take_step(step, self.tick_duration);
+        }
+
+        // interactions that ran out or were abandoned end before new ones start,
+        // so a pair that goes right back to it ends and starts again
+        let ongoing = interaction::events(&self.creatures);
+        let is_in = |events: &[InteractionEvent], event: &InteractionEvent| {
+            events
+                .iter()
+                .any(|other| other.interaction == event.interaction && other.ids == event.ids)
+        };
+        self.events.extend(
+            interactions
+                .iter()
+                .filter(|event| !is_in(&ongoing, event))
+                .map(|event| Event::InteractionEnded {
+                    interaction: event.interaction,
+                    ids: event.ids.clone(),
+                }),
+        );
+
+        interaction::start(&mut self.creatures, rng);
+        let started = interaction::events(&self.creatures);
+        self.events.extend(
+            started
+                .iter()
+                .filter(|event| !is_in(&ongoing, event))
+                .map(|event| Event::InteractionStarted {
+                    interaction: event.interaction,
+                    ids: event.ids.clone(),
+                }),
+        );
+
+        // treats go stale, or get eaten by whoever reaches them
+        let creatures = &self.creatures;
+        self.treats.retain_mut(|treat| {
+            treat.marker.wear_off() && !creatures.iter().any(|creature| treat.is_eaten_by(creature))
+        });
+        self.attractors
+            .retain_mut(|attractor| attractor.marker.wear_off());
+
+        Ok(&self.events)
+    }
+
+    pub fn creatures(&self) -> Vec<&Creature> {
+        self.creatures.iter().collect()
+    }
+
+    pub fn get_creature(&self, id: &str) -> Option<&Creature> {
+        self.creatures.iter().find(|creature| creature.id == id)
+    }
+
+    fn get_creature_mut(&mut self, id: &str) -> Result<&mut Creature, String> {
+        self.creatures
+            .iter_mut()
+            .find(|creature| creature.id == id)
+            .ok_or(format!("No creature {} in the kennel", id))
+    }
+
+    /**
+     * Puts a creature somewhere it isn't colliding with anything and returns where.
+     */
+    pub fn add_creature<R: Rng + ?Sized>(
+        &mut self,
+        creature: Creature,
+        rng: &mut R,
+    ) -> Result<Vec2, String> {
+        if self.get_creature(&creature.id).is_some() {
+            return Err(format!("Creature {} is already in the kennel", creature.id));
+        }
+
+        let position = free_position(&self.creatures, &creature, rng)?;
+        self.insert_creature(creature, position);
+        Ok(position)
+    }
+
+    /**
+     * Puts a creature at a position that's already known to be free.
+     */
+    fn insert_creature(&mut self, creature: Creature, position: Vec2) {
+        self.pending_events.push(Event::CreatureAdded {
+            id: creature.id.clone(),
+        });
+        self.creatures.push(creature.set_position(position));
+    }
+
+    pub fn remove_creature(&mut self, id: &str) -> Result<Creature, String> {
+        let idx = self
             .creatures
             .iter()
-            .map(|creature| creature.with_next_state(rng))
-            .collect();
+            .position(|creature| creature.id == id)
+            .ok_or(format!("No creature {} in the kennel", id))?;
+        self.pending_events
+            .push(Event::CreatureRemoved { id: id.to_string() });
+        Ok(self.creatures.remove(idx))
+    }
+
+    /**
+     * Holds a creature in a state for the next `ticks` ticks.
+     */
+    pub fn force_state(
+        &mut self,
+        id: &str,
+        state: creature::State,
+        ticks: usize,
+    ) -> Result<(), String> {
+        let creature = self.get_creature_mut(id)?;
+        let from = creature.creature_state.clone();
+        creature.force_state(state, ticks);
+        let to = creature.creature_state.clone();
+
+        if from != to {
+            self.pending_events.push(Event::StateChanged {
+                id: id.to_string(),
+                from,
+                to,
+            });
+        }
+        Ok(())
+    }
+
+    /**
+     * Queues a command for a creature, if the token belongs to its owner.
+     */
+    pub fn command(
+        &mut self,
+        owners: &Owners,
+        token: &str,
+        id: &str,
+        command: creature::Command,
+    ) -> Result<(), String> {
+        if !owners.is_owner(token, id) {
+            return Err(format!("Not allowed to command creature {}", id));
+        }
+        command.validate()?;
 
-        let mut arena: Arena = Arena::new();
-        for creature in new_creatures.iter() {
-            let step = creature.get_next_step(&center_of_mass);
-            arena.add(step);
+        let creature = self.get_creature_mut(id)?;
+        if creature.commands.len() >= MAX_QUEUED_COMMANDS {
+            return Err(format!(
+                "Creature {} already has {} commands to get through",
+                id, MAX_QUEUED_COMMANDS
+            ));
         }
+        creature.commands.push_back(command);
+        Ok(())
+    }
+
+    /**
+     * Drops a treat that following creatures go for until it's eaten or `ticks` ticks pass.
+     */
+    pub fn drop_treat(&mut self, position: Vec2, ticks: usize) -> Result<(), String> {
+        let marker = Marker::new("Treat", position, ticks)?;
+        self.treats.push(Treat { marker });
+        Ok(())
+    }
 
-        let steps = arena.into_vec();
-        let repositioned_creatures: Vec<_> = zip(new_creatures, steps)
-            .map(|(creature, step)| creature.step(step))
+    /**
+     * Places a spot that pulls nearby following and fleeing creatures toward it
+     * for the next `ticks` ticks, `strength` times as hard as they're drawn to each other.
+     */
+    pub fn place_attractor(
+        &mut self,
+        position: Vec2,
+        strength: f64,
+        ticks: usize,
+    ) -> Result<(), String> {
+        self.place(position, strength, ticks)
+    }
+
+    /**
+     * Same as `place_attractor`, but pushes creatures away instead.
+     */
+    pub fn place_repeller(
+        &mut self,
+        position: Vec2,
+        strength: f64,
+        ticks: usize,
+    ) -> Result<(), String> {
+        self.place(position, -strength, ticks)
+    }
+
+    fn place(&mut self, position: Vec2, strength: f64, ticks: usize) -> Result<(), String> {
+        let marker = Marker::new("Attractor", position, ticks)?;
+        if !(0.0..=MAX_ATTRACTOR_STRENGTH).contains(&strength.abs()) {
+            return Err(format!(
+                "Attractor strength must be between 0 and {}, got {}",
+                MAX_ATTRACTOR_STRENGTH,
+                strength.abs()
+            ));
+        }
+
+        self.attractors.push(Attractor { marker, strength });
+        Ok(())
+    }
+
+    pub fn attractors(&self) -> &[Attractor] {
+        &self.attractors
+    }
+
+    /**
+     * Opens doorways in the walls that creatures walk into instead of bumping.
+     * Where they go from there is up to whoever holds the rooms, like a `KennelClub`.
+     */
+    pub fn set_doorways(&mut self, doorways: Vec<Doorway>) -> Result<(), String> {
+        for doorway in doorways.iter() {
+            doorway.opening.validate()?;
+        }
+        self.openings = doorways.iter().map(|doorway| doorway.opening).collect();
+        self.doorways = doorways;
+        Ok(())
+    }
+
+    pub fn doorways(&self) -> &[Doorway] {
+        &self.doorways
+    }
+
+    pub fn treats(&self) -> &[Treat] {
+        &self.treats
+    }
+
+    /**
+     * What happened during the tick that made this kennel,
+     * along with anything done to it just before, like adding creatures.
+     */
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /**
+     * The interactions going on between creatures, once per pair.
+     */
+    pub fn interactions(&self) -> Vec<InteractionEvent> {
+        interaction::events(&self.creatures)
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let creatures = self
+            .creatures
+            .iter()
+            .map(|creature| CreatureSnapshot {
+                id: creature.id.clone(),
+                position: creature.position,
+                state: creature.creature_state.clone(),
+                sprite_state: creature.sprite_state,
+            })
             .collect();
 
-        Ok(Kennel {
-            creatures: repositioned_creatures,
-        })
+        Snapshot {
+            creatures,
+            treats: self.treats.clone(),
+            attractors: self.attractors.clone(),
+            interactions: self.interactions(),
+        }
     }
 
-    pub fn creatures(&self) -> Vec<&Creature> {
-        self.creatures.iter().collect()
+    /**
+     * What renderers draw of the kennel today.
+     */
+    pub fn scene(&self) -> Scene<'_> {
+        Scene {
+            treats: self
+                .treats
+                .iter()
+                .map(|treat| treat.marker.position)
+                .collect(),
+            attractors: self.attractors.clone(),
+            ..Scene::new(self.creatures(), &self.theme)
+        }
     }
 
     /**
@@ -192,11 +690,15 @@ impl Kennel {
         }
     }
 
+    /**
+     * Draws the creatures to the terminal, redrawing only what changed since its last draw.
+     */
+    pub fn draw<W: Write>(&self, terminal: &mut Terminal<W>) -> Result<(), String> {
+        terminal.draw(&self.scene(), &ImageOptions::from(&self.theme))
+    }
+
     pub fn get_sprite(&self, id: &str) -> Option<&Sprite> {
-        self.creatures
-            .iter()
-            .find(|creature| creature.id == id)
-            .map(|creature| creature.sprite())
+        self.get_creature(id).map(|creature| creature.sprite())
     }
 
     pub fn get_sprite_by(
@@ -205,53 +707,75 @@ impl Kennel {
         sprite_state: &sprite::State,
         frame: &usize,
     ) -> Option<&Sprite> {
-        self.creatures
-            .iter()
-            .find(|creature| creature.id == id)
+        self.get_creature(id)
             .map(|creature| creature.sprite_sheet.get_sprite(sprite_state, *frame))
     }
 
+    pub fn get_sprite_at(
+        &self,
+        id: &str,
+        sprite_state: &sprite::State,
+        elapsed: &Duration,
+    ) -> Option<&Sprite> {
+        self.get_creature(id)
+            .map(|creature| creature.sprite_sheet.get_sprite_at(sprite_state, *elapsed))
+    }
+
     pub fn get_image(
         &self,
         canvas_width: u32,
         canvas_height: u32,
         image_format: ImageFormat,
     ) -> Result<Vec<u8>, String> {
-        let mut canvas = RgbaImage::new(canvas_width, canvas_height);
-        let canvas_scale_factor = u32::min(canvas_width, canvas_height) as f64;
-
-        for creature in self.creatures() {
-            let sprite = creature.sprite();
-
-            // scale creature sprite
-            let sprite_scale_factor =
-                2.0 * creature.radius * canvas_scale_factor * sprite.scale_factor();
-            let image = sprite.get_scaled_image(sprite_scale_factor);
-
-            // get canvas position, WRT canvas pixel units
-            let canvas_position = canvas_scale_factor * &creature.position - &creature.radius;
+        self.get_image_with(
+            canvas_width,
+            canvas_height,
+            image_format,
+            &ImageOptions::from(&self.theme),
+        )
+    }
 
-            let x_start = (canvas_position.x as u32).clamp(0, canvas_width - image.width() - 1);
-            let y_start = (canvas_position.y as u32).clamp(0, canvas_height - image.height() - 1);
+    /**
+     * Same as `get_image`, drawing the layers in the options.
+     */
+    pub fn get_image_with(
+        &self,
+        canvas_width: u32,
+        canvas_height: u32,
+        image_format: ImageFormat,
+        options: &ImageOptions,
+    ) -> Result<Vec<u8>, String> {
+        let canvas = render::raster::render(&self.scene(), canvas_width, canvas_height, options);
+        render::raster::encode(canvas, image_format)
+    }
 
-            let image_buffer = image
-                .as_rgba8()
-                .ok_or("Error representing sprite with 32bit RGBA")?;
-            overlay(&mut canvas, image_buffer, x_start.into(), y_start.into());
-        }
+    /**
+     * Renders the kennel as an SVG document, with each creature linking to its site.
+     */
+    pub fn get_svg(&self) -> Result<String, String> {
+        self.get_svg_with(&ImageOptions::from(&self.theme))
+    }
 
-        let canvas = DynamicImage::from(canvas);
-        let mut image_buffer = BufWriter::new(Cursor::new(Vec::new()));
-        canvas
-            .write_to(&mut image_buffer, image_format)
-            .map_err(|_| "Error writing kennel image")?;
+    /**
+     * Same as `get_svg`, drawing the layers in the options.
+     */
+    pub fn get_svg_with(&self, options: &ImageOptions) -> Result<String, String> {
+        render::svg::render(&self.scene(), options)
+    }
 
-        let bytes = image_buffer
-            .into_inner()
-            .map(Cursor::into_inner)
-            .map_err(|_| "Error writing kennel image to bytes")?;
+    /**
+     * Where each creature is in an image from `get_image` of the same size,
+     * front to back, for linking creatures to their sites.
+     */
+    pub fn get_hit_regions(&self, canvas_width: u32, canvas_height: u32) -> Vec<HitRegion> {
+        render::map::hit_regions(&self.creatures(), canvas_width, canvas_height)
+    }
 
-        Ok(bytes)
+    /**
+     * Same as `get_hit_regions`, as an HTML `<map>` named `name`.
+     */
+    pub fn get_image_map(&self, canvas_width: u32, canvas_height: u32, name: &str) -> String {
+        render::map::html_map(&self.get_hit_regions(canvas_width, canvas_height), name)
     }
 }
 
@@ -260,6 +784,7 @@ mod tests {
     use super::*;
     use crate::creature::Metadata;
     use rand::{SeedableRng, rngs::SmallRng};
+    use std::time::SystemTime;
 
     static RNG_SEED: u64 = 1;
 
@@ -271,11 +796,346 @@ mod tests {
         assert!(kennel_result.is_err());
     }
 
+    #[test]
+    fn test_force_state() {
+        let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+        let creature: Creature = Metadata::mock(0.1).into();
+        let id = creature.id.clone();
+        let mut kennel = Kennel::new(vec![creature], &mut rng).unwrap();
+
+        kennel.force_state(&id, creature::State::Sleep, 3).unwrap();
+        for _ in 0..3 {
+            kennel = kennel.next(&mut rng).unwrap().0;
+            assert_eq!(kennel.creatures[0].creature_state, creature::State::Sleep);
+        }
+        assert!(kennel.creatures[0].forced_state.is_none());
+        assert!(
+            kennel
+                .force_state("nobody", creature::State::Sleep, 3)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_tick_reuses_arena() {
+        let creatures = || (1..=5).map(|_| Metadata::mock(0.1).into()).collect();
+        let mut kennel = Kennel::new(creatures(), &mut SmallRng::seed_from_u64(RNG_SEED)).unwrap();
+        let mut fresh = kennel.clone();
+
+        let (mut rng, mut fresh_rng) = (
+            SmallRng::seed_from_u64(RNG_SEED),
+            SmallRng::seed_from_u64(RNG_SEED),
+        );
+        for tick in 0..50 {
+            // the arena is cleared and refilled even as the creatures in it change
+            if tick == 20 {
+                let id = kennel.creatures[1].id.clone();
+                kennel.remove_creature(&id).unwrap();
+                fresh.remove_creature(&id).unwrap();
+            }
+            if tick == 35 {
+                let creature: Creature = Metadata::mock(0.1).into();
+                kennel.add_creature(creature.clone(), &mut rng).unwrap();
+                fresh.add_creature(creature, &mut fresh_rng).unwrap();
+            }
+
+            kennel.tick(&mut rng).unwrap();
+            fresh.arena = Arena::new();
+            fresh.tick(&mut fresh_rng).unwrap();
+            assert_eq!(kennel.events, fresh.events);
+        }
+
+        assert_eq!(kennel.creatures.len(), 5);
+        for (creature, fresh) in zip(kennel.creatures, fresh.creatures) {
+            assert_eq!(creature.position, fresh.position);
+            assert_eq!(creature.creature_state, fresh.creature_state);
+        }
+    }
+
+    #[test]
+    fn test_treats() {
+        let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+        let creature: Creature = Metadata::mock(0.1).into();
+        let mut kennel = Kennel::new(vec![creature], &mut rng).unwrap();
+        let position = kennel.creatures[0].position;
+
+        // one treat under the creature gets eaten, one out of reach goes stale
+        kennel.drop_treat(position, 10).unwrap();
+        kennel
+            .drop_treat(Vec2::new(position.x, 1.0 - position.y), 2)
+            .unwrap();
+        assert!(kennel.drop_treat(Vec2::new(1.5, 0.5), 10).is_err());
+        assert!(kennel.drop_treat(position, 0).is_err());
+
+        kennel = kennel.next(&mut rng).unwrap().0;
+        assert_eq!(kennel.treats().len(), 1);
+        kennel = kennel.next(&mut rng).unwrap().0;
+        assert!(kennel.treats().is_empty());
+    }
+
+    #[test]
+    fn test_attractors() {
+        let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+        let mut metadata = Metadata::mock(0.05);
+        metadata.step_size = 0.1;
+        let creature: Creature = metadata.into();
+        let id = creature.id.clone();
+        let mut kennel = Kennel::new(vec![creature], &mut rng).unwrap();
+        kennel.creatures[0].position = Vec2::new(0.3, 0.5);
+        kennel
+            .force_state(&id, creature::State::Follow, 10)
+            .unwrap();
+
+        // following toward the middle, the creature veers up toward an attractor above it
+        let mut attracted = kennel.clone();
+        attracted
+            .place_attractor(Vec2::new(0.3, 0.3), 2.0, 1)
+            .unwrap();
+        attracted.tick(&mut rng).unwrap();
+        assert!(attracted.creatures[0].position.y < 0.5);
+        assert!(attracted.attractors().is_empty());
+
+        // and down, away from a repeller
+        let mut repelled = kennel.clone();
+        repelled
+            .place_repeller(Vec2::new(0.3, 0.3), 2.0, 1)
+            .unwrap();
+        repelled.tick(&mut rng).unwrap();
+        assert!(repelled.creatures[0].position.y > 0.5);
+
+        assert!(kennel.place_attractor(Vec2::new(1.5, 0.5), 1.0, 1).is_err());
+        assert!(
+            kennel
+                .place_repeller(Vec2::new(0.5, 0.5), MAX_ATTRACTOR_STRENGTH + 1.0, 1)
+                .is_err()
+        );
+        assert!(
+            kennel
+                .place_attractor(Vec2::new(0.5, 0.5), f64::NAN, 1)
+                .is_err()
+        );
+        assert!(kennel.place_attractor(Vec2::new(0.5, 0.5), 1.0, 0).is_err());
+    }
+
+    #[test]
+    fn test_replace_creatures() {
+        let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+        let stays: Creature = Metadata::mock(0.1).into();
+        let leaves: Creature = Metadata::mock(0.1).into();
+        let (stays_id, leaves_id) = (stays.id.clone(), leaves.id.clone());
+        let mut kennel = Kennel::new(vec![stays, leaves], &mut rng).unwrap();
+        kennel
+            .force_state(&stays_id, creature::State::Sleep, 5)
+            .unwrap();
+        let position = kennel.creatures[0].position;
+
+        let mut renamed: Creature = Metadata::mock(0.1).into();
+        renamed.id = stays_id.clone();
+        renamed.display_name = "renamed".to_string();
+        let joins: Creature = Metadata::mock(0.1).into();
+        let joins_id = joins.id.clone();
+
+        let reload = kennel
+            .replace_creatures(vec![renamed, joins], &mut rng)
+            .unwrap();
+        assert_eq!(reload.added, vec![joins_id]);
+        assert_eq!(reload.removed, vec![leaves_id]);
+        assert_eq!(reload.updated, vec![stays_id.clone()]);
+
+        let stayed = &kennel.creatures[0];
+        assert_eq!(stayed.id, stays_id);
+        assert_eq!(stayed.display_name, "renamed");
+        assert_eq!(stayed.creature_state, creature::State::Sleep);
+        assert_eq!(stayed.position.x, position.x);
+        assert_eq!(stayed.position.y, position.y);
+        assert!(
+            !stayed
+                .as_collidable()
+                .is_colliding(&kennel.creatures[1].as_collidable())
+        );
+
+        // a creature too big to fit anywhere leaves the kennel untouched
+        let huge: Creature = Metadata::mock(100.0).into();
+        assert!(kennel.replace_creatures(vec![huge], &mut rng).is_err());
+        assert_eq!(kennel.creatures.len(), 2);
+    }
+
+    #[test]
+    fn test_reload_sprites() {
+        let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+        let dir = std::env::temp_dir().join(format!("kennel-reload-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("dot")).unwrap();
+        std::fs::write(
+            dir.join("metadata.json"),
+            r#"[{"id": "dot", "display_name": "dot", "url": "https://example.com",
+                 "step_size": 0.1, "radius": 0.1, "sprites": {"idle": ["idle.png"]}}]"#,
+        )
+        .unwrap();
+
+        let sprite = dir.join("dot").join("idle.png");
+        let draw = |color: [u8; 4], modified: SystemTime| {
+            image::RgbaImage::from_pixel(1, 1, image::Rgba(color))
+                .save(&sprite)
+                .unwrap();
+            let file = std::fs::File::options().write(true).open(&sprite).unwrap();
+            file.set_modified(modified).unwrap();
+        };
+        let shown = |kennel: &Kennel| {
+            let creature = &kennel.creatures[0];
+            let image = creature.sprite().as_image().to_rgba8();
+            (image.get_pixel(0, 0).0, creature.sprite().bytes().unwrap())
+        };
+
+        let loaded_at = SystemTime::now();
+        draw([255, 0, 0, 255], loaded_at);
+        let mut kennel = Kennel::load(&dir, &mut rng).unwrap();
+        assert_eq!(shown(&kennel).0, [255, 0, 0, 255]);
+
+        // the old sprite is still alive in the kennel, so it must not be handed back
+        draw([0, 0, 255, 255], loaded_at + Duration::from_secs(1));
+        kennel.reload(&dir, &mut rng).unwrap();
+        let (pixel, bytes) = shown(&kennel);
+        assert_eq!(pixel, [0, 0, 255, 255]);
+        assert_eq!(bytes, std::fs::read(&sprite).unwrap());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_rooms() {
+        let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+        let dir = std::env::temp_dir().join(format!("kennel-rooms-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("dot")).unwrap();
+        image::RgbaImage::new(1, 1)
+            .save(dir.join("dot").join("idle.png"))
+            .unwrap();
+        std::fs::write(
+            dir.join("metadata.json"),
+            r#"[{"id": "dot", "display_name": "dot", "url": "https://example.com", "room": "attic",
+                 "step_size": 0.1, "radius": 0.1, "sprites": {"idle": ["idle.png"]}}]"#,
+        )
+        .unwrap();
+
+        // rooms belong to a club, not lumped into one kennel
+        assert!(Kennel::load(&dir, &mut rng).is_err());
+        assert!(
+            KennelClub::load(&dir, &mut rng)
+                .unwrap()
+                .room("attic")
+                .is_some()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_add_remove_creature() {
+        let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+        let mut kennel = Kennel::new(vec![Metadata::mock(0.2).into()], &mut rng).unwrap();
+
+        let creature: Creature = Metadata::mock(0.2).into();
+        let id = creature.id.clone();
+        let duplicate = Creature {
+            id: id.clone(),
+            ..Metadata::mock(0.1).into()
+        };
+        let position = kennel.add_creature(creature, &mut rng).unwrap();
+        assert!(kennel.add_creature(duplicate, &mut rng).is_err());
+        assert!(
+            kennel
+                .add_creature(Metadata::mock(100.0).into(), &mut rng)
+                .is_err()
+        );
+
+        let added = kennel.get_creature(&id).unwrap();
+        assert_eq!(added.position.x, position.x);
+        assert_eq!(added.position.y, position.y);
+        assert!(
+            !added
+                .as_collidable()
+                .is_colliding(&kennel.creatures[0].as_collidable())
+        );
+
+        assert_eq!(kennel.remove_creature(&id).unwrap().id, id);
+        assert!(kennel.get_creature(&id).is_none());
+        assert!(kennel.remove_creature(&id).is_err());
+        assert_eq!(kennel.creatures().len(), 1);
+    }
+
+    #[test]
+    fn test_events() {
+        let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+        let creature: Creature = Metadata::mock(0.1).into();
+        let id = creature.id.clone();
+        let mut kennel = Kennel::new(vec![], &mut rng).unwrap();
+
+        // what's done between ticks is told with the next one
+        kennel.add_creature(creature, &mut rng).unwrap();
+        kennel.force_state(&id, creature::State::Sleep, 2).unwrap();
+        let events = kennel.tick(&mut rng).unwrap();
+        assert_eq!(
+            events,
+            [
+                Event::CreatureAdded { id: id.clone() },
+                Event::StateChanged {
+                    id: id.clone(),
+                    from: creature::State::Idle,
+                    to: creature::State::Sleep,
+                },
+            ]
+            .as_slice()
+        );
+
+        kennel.tick(&mut rng).unwrap();
+        assert!(kennel.events().is_empty());
+
+        kennel.remove_creature(&id).unwrap();
+        let (kennel, events) = kennel.next(&mut rng).unwrap();
+        assert_eq!(events, vec![Event::CreatureRemoved { id }]);
+        assert_eq!(kennel.events(), events.as_slice());
+    }
+
+    #[test]
+    fn test_command() {
+        let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+        let mut creature: Creature = Metadata::mock(0.05).into();
+        creature.step_size = 0.1;
+        let id = creature.id.clone();
+        let mut kennel = Kennel::new(vec![creature], &mut rng).unwrap();
+        kennel.creatures[0].position = Vec2::new(0.5, 0.5);
+
+        let mut owners = Owners::default();
+        owners.set_token(&id, "token").unwrap();
+        let go_to = creature::Command::GoTo {
+            position: Vec2::new(0.7, 0.5),
+            ticks: 10,
+        };
+        let wave = creature::Command::Wave { ticks: 2 };
+        assert!(kennel.command(&owners, "wrong", &id, wave.clone()).is_err());
+        kennel.command(&owners, "token", &id, go_to).unwrap();
+        kennel.command(&owners, "token", &id, wave.clone()).unwrap();
+
+        // two steps there, then waving in place until the wave runs out
+        for _ in 0..2 {
+            kennel.tick(&mut rng).unwrap();
+        }
+        assert!((kennel.creatures[0].position.x - 0.7).abs() < 1e-9);
+        for _ in 0..2 {
+            kennel.tick(&mut rng).unwrap();
+            assert_eq!(kennel.creatures[0].sprite_state, sprite::State::Wave);
+        }
+        assert!((kennel.creatures[0].position.x - 0.7).abs() < 1e-9);
+
+        for _ in 0..MAX_QUEUED_COMMANDS {
+            kennel.command(&owners, "token", &id, wave.clone()).unwrap();
+        }
+        assert!(kennel.command(&owners, "token", &id, wave).is_err());
+    }
+
     #[test]
     fn test_new_collisions() {
         let mut rng = SmallRng::seed_from_u64(RNG_SEED);
         let metadata: Vec<_> = (1..=10)
-            .into_iter()
             .map(|radius| Metadata::mock((radius as f64) / 100.0).into())
             .collect();
 
@@ -288,7 +1148,7 @@ mod tests {
 
         for collidable_combination in collidable_combinations {
             let (c1, c2) = (
-                collidable_combination.get(0).unwrap(),
+                collidable_combination.first().unwrap(),
                 collidable_combination.get(1).unwrap(),
             );
             if c1.is_colliding(c2) {
@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::time::Duration;
+
+use itertools::Itertools;
+use rand::Rng;
+
+use crate::creature::Creature;
+use crate::kennel::{DEFAULT_ROOM, Event, Kennel, KennelClub, Migration};
+use crate::math::Vec2;
+use crate::render::{ImageOptions, Scene, Terminal};
+
+/**
+ * How many ticks `Simulation::advance` runs at most per call.
+ * If rendering falls further behind than that, the simulation slows down instead of
+ * spending ever longer catching up.
+ */
+pub static DEFAULT_MAX_SUBSTEPS: usize = 8;
+
+/**
+ * Runs a kennel in real time, a fixed tick at a time, however often it's advanced.
+ * Renderers draw `scene`, where creatures sit between where they were on the last two ticks,
+ * so they move smoothly at any frame rate.
+ * With a whole club, every room runs and the one being watched is the one drawn.
+ *
+ * Only walking and animations follow the clock. How long states, forced states, commands,
+ * interactions, treats and attractors last is still counted in ticks,
+ * so they last longer in real time the longer each tick takes.
+ */
+pub struct Simulation {
+    club: KennelClub,
+    room: String,
+    previous_positions: HashMap<String, Vec2>,
+    accumulator: Duration,
+    max_substeps: usize,
+    events: Vec<Event>,
+    migrations: Vec<Migration>,
+    drawn: Vec<Creature>,
+    is_drawn_stale: bool,
+}
+
+fn watched<'a>(club: &'a KennelClub, room: &str) -> &'a Kennel {
+    club.room(room).expect("The watched room stays in the club")
+}
+
+impl Simulation {
+    pub fn new(kennel: Kennel) -> Result<Self, String> {
+        let mut club = KennelClub::new();
+        club.add_room(DEFAULT_ROOM, kennel)?;
+        Simulation::of_club(club, DEFAULT_ROOM)
+    }
+
+    /**
+     * Runs every room of the club, watching `room`.
+     * The rooms tick together, so they all have to take the same time per tick.
+     */
+    pub fn of_club(club: KennelClub, room: &str) -> Result<Self, String> {
+        if club.room(room).is_none() {
+            return Err(format!("No room {} in the club", room));
+        }
+        if club
+            .rooms()
+            .any(|(_, kennel)| kennel.tick_duration().is_zero())
+        {
+            return Err("Unable to simulate a kennel whose ticks take no time".to_string());
+        }
+        if !club
+            .rooms()
+            .map(|(_, kennel)| kennel.tick_duration())
+            .all_equal()
+        {
+            return Err("Unable to simulate rooms whose ticks take different times".to_string());
+        }
+
+        let mut simulation = Simulation {
+            club,
+            room: room.to_string(),
+            previous_positions: HashMap::new(),
+            accumulator: Duration::ZERO,
+            max_substeps: DEFAULT_MAX_SUBSTEPS,
+            events: vec![],
+            migrations: vec![],
+            drawn: vec![],
+            is_drawn_stale: true,
+        };
+        simulation.remember_positions();
+        Ok(simulation)
+    }
+
+    pub fn with_max_substeps(self, max_substeps: usize) -> Self {
+        Simulation {
+            max_substeps,
+            ..self
+        }
+    }
+
+    /**
+     * The room being watched.
+     */
+    pub fn kennel(&self) -> &Kennel {
+        watched(&self.club, &self.room)
+    }
+
+    pub fn club(&self) -> &KennelClub {
+        &self.club
+    }
+
+    pub fn room(&self) -> &str {
+        &self.room
+    }
+
+    /**
+     * For poking at the kennel between ticks, e.g. to drop a treat.
+     */
+    pub fn kennel_mut(&mut self) -> &mut Kennel {
+        self.is_drawn_stale = true;
+        self.club
+            .room_mut(&self.room)
+            .expect("The watched room stays in the club")
+    }
+
+    fn remember_positions(&mut self) {
+        let creatures = watched(&self.club, &self.room).creatures();
+        self.previous_positions
+            .retain(|id, _| creatures.iter().any(|creature| &creature.id == id));
+        for creature in creatures {
+            match self.previous_positions.get_mut(&creature.id) {
+                Some(position) => *position = creature.position,
+                None => {
+                    self.previous_positions
+                        .insert(creature.id.clone(), creature.position);
+                }
+            }
+        }
+    }
+
+    /**
+     * What happened in the watched room over every tick run by the last `advance` or `step`.
+     */
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /**
+     * Who wandered between rooms over every tick run by the last `advance` or `step`.
+     */
+    pub fn migrations(&self) -> &[Migration] {
+        &self.migrations
+    }
+
+    fn tick<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Result<(), String> {
+        self.remember_positions();
+        let migrations = self.club.tick(rng)?;
+        self.migrations.extend_from_slice(migrations);
+        self.events
+            .extend_from_slice(watched(&self.club, &self.room).events());
+        self.is_drawn_stale = true;
+        Ok(())
+    }
+
+    /**
+     * Runs a single tick right away, whatever time it is.
+     */
+    pub fn step<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Result<(), String> {
+        self.events.clear();
+        self.migrations.clear();
+        self.tick(rng)
+    }
+
+    /**
+     * Lets `elapsed` pass, running as many ticks as fit and returning how many that was.
+     * Time left over carries on to the next call.
+     */
+    pub fn advance<R: Rng + ?Sized>(
+        &mut self,
+        elapsed: Duration,
+        rng: &mut R,
+    ) -> Result<usize, String> {
+        let tick_duration = self.kennel().tick_duration();
+        self.accumulator += elapsed;
+        self.events.clear();
+        self.migrations.clear();
+
+        let mut substeps = 0;
+        while self.accumulator >= tick_duration {
+            if substeps == self.max_substeps {
+                self.accumulator = Duration::ZERO;
+                break;
+            }
+
+            self.tick(rng)?;
+            self.accumulator -= tick_duration;
+            substeps += 1;
+        }
+
+        Ok(substeps)
+    }
+
+    /**
+     * How far along the next tick is, between 0 and 1.
+     */
+    pub fn alpha(&self) -> f64 {
+        self.accumulator.as_secs_f64() / self.kennel().tick_duration().as_secs_f64()
+    }
+
+    /**
+     * Where the creature would be drawn right now, between where it was on the last two ticks.
+     */
+    pub fn interpolated_position(&self, id: &str) -> Option<Vec2> {
+        let current = self.kennel().get_creature(id)?.position;
+        let previous = self.previous_positions.get(id).unwrap_or(&current);
+        Some(previous + &(self.alpha() * &(&current - previous)))
+    }
+
+    /**
+     * The kennel as it looks right now, with every creature at its interpolated position.
+     * Creatures are only copied again once a tick or a change to the kennel went by,
+     * in between frames just move them along.
+     */
+    pub fn scene(&mut self) -> Scene<'_> {
+        if self.is_drawn_stale {
+            self.drawn
+                .clone_from(&watched(&self.club, &self.room).creatures);
+            self.is_drawn_stale = false;
+        }
+        for idx in 0..self.drawn.len() {
+            if let Some(position) = self.interpolated_position(&self.drawn[idx].id) {
+                self.drawn[idx].position = position;
+            }
+        }
+
+        let kennel = watched(&self.club, &self.room);
+        Scene {
+            creatures: self.drawn.iter().collect(),
+            ..kennel.scene()
+        }
+    }
+
+    pub fn draw<W: Write>(&mut self, terminal: &mut Terminal<W>) -> Result<(), String> {
+        let options = ImageOptions::from(self.kennel().theme());
+        terminal.draw(&self.scene(), &options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, rngs::SmallRng};
+
+    use super::*;
+    use crate::creature::{Metadata, State};
+    use crate::kennel::Door;
+    use crate::physics::{Opening, Wall};
+
+    static RNG_SEED: u64 = 1;
+
+    fn simulation(tick_duration: Duration) -> (Simulation, String) {
+        let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+        let mut creature: Creature = Metadata::mock(0.05).into();
+        creature.step_size = 0.1;
+        let id = creature.id.clone();
+
+        let mut kennel = Kennel::new(vec![creature], &mut rng)
+            .unwrap()
+            .with_tick_duration(tick_duration);
+        kennel.creatures[0].position = Vec2::new(0.2, 0.5);
+        kennel.drop_treat(Vec2::new(0.8, 0.5), 100).unwrap();
+        kennel.force_state(&id, State::Follow, 100).unwrap();
+        (Simulation::new(kennel).unwrap(), id)
+    }
+
+    #[test]
+    fn test_advance() {
+        let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+        let (mut simulation, id) = simulation(Duration::from_millis(100));
+
+        assert_eq!(
+            simulation
+                .advance(Duration::from_millis(250), &mut rng)
+                .unwrap(),
+            2
+        );
+        assert!((simulation.alpha() - 0.5).abs() < 1e-9);
+
+        // two ticks of a tenth of a second at 0.1 per second, and halfway through the third
+        let position = simulation.kennel().get_creature(&id).unwrap().position;
+        assert!((position.x - 0.22).abs() < 1e-9);
+        let interpolated = simulation.interpolated_position(&id).unwrap();
+        assert!((interpolated.x - 0.215).abs() < 1e-9);
+        let drawn = simulation.scene().creatures[0].position;
+        assert!((drawn.x - 0.215).abs() < 1e-9);
+
+        // creatures moved by hand are drawn where they were put
+        simulation.kennel_mut().creatures[0].position = Vec2::new(0.6, 0.5);
+        simulation.previous_positions.clear();
+        assert_eq!(
+            simulation.scene().creatures[0].position,
+            Vec2::new(0.6, 0.5)
+        );
+
+        // falling far behind runs only so many ticks and drops the rest
+        let substeps = simulation
+            .with_max_substeps(3)
+            .advance(Duration::from_secs(10), &mut rng)
+            .unwrap();
+        assert_eq!(substeps, 3);
+    }
+
+    #[test]
+    fn test_speed_per_second() {
+        let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+        let (mut slow_ticks, id) = simulation(Duration::from_secs(1));
+        let (mut fast_ticks, fast_id) = simulation(Duration::from_millis(250));
+
+        slow_ticks
+            .advance(Duration::from_secs(2), &mut rng)
+            .unwrap();
+        fast_ticks
+            .advance(Duration::from_secs(2), &mut rng)
+            .unwrap();
+
+        let slow = slow_ticks.kennel().get_creature(&id).unwrap().position;
+        let fast = fast_ticks.kennel().get_creature(&fast_id).unwrap().position;
+        assert!((slow.x - fast.x).abs() < 1e-9);
+        assert!(
+            Simulation::new(
+                slow_ticks
+                    .kennel()
+                    .clone()
+                    .with_tick_duration(Duration::ZERO)
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_of_club() {
+        let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+        let mut metadata = Metadata::mock(0.1);
+        metadata.step_size = 0.5;
+        let id = metadata.id.clone();
+        let mut inside = Kennel::new(vec![metadata.into()], &mut rng).unwrap();
+        inside.creatures[0].position = Vec2::new(0.85, 0.5);
+        inside.force_state(&id, State::Flee, 100).unwrap();
+
+        let mut club = KennelClub::new();
+        club.add_room("inside", inside).unwrap();
+        club.add_room("garden", Kennel::new(vec![], &mut rng).unwrap())
+            .unwrap();
+        let opening = |wall: Wall| Opening {
+            wall,
+            from: 0.3,
+            to: 0.7,
+        };
+        club.add_door(Door {
+            rooms: ["inside".to_string(), "garden".to_string()],
+            openings: Some([opening(Wall::East), opening(Wall::West)]),
+        })
+        .unwrap();
+        assert!(Simulation::of_club(club.clone(), "attic").is_err());
+        let mut slow_garden = club.clone();
+        let garden = slow_garden.remove_room("garden").unwrap();
+        slow_garden
+            .add_room("garden", garden.with_tick_duration(Duration::from_secs(2)))
+            .unwrap();
+        assert!(Simulation::of_club(slow_garden, "inside").is_err());
+
+        // the creature flees out of the watched room, and the garden keeps it running
+        let mut simulation = Simulation::of_club(club, "inside").unwrap();
+        simulation
+            .advance(Duration::from_secs(1), &mut rng)
+            .unwrap();
+        assert_eq!(
+            simulation.migrations(),
+            [Migration {
+                id: id.clone(),
+                from: "inside".to_string(),
+                to: "garden".to_string(),
+            }]
+        );
+        assert!(simulation.kennel().creatures().is_empty());
+        assert!(simulation.scene().creatures.is_empty());
+
+        simulation.step(&mut rng).unwrap();
+        assert!(simulation.migrations().is_empty());
+        assert_eq!(simulation.club().find(&id).unwrap().0, "garden");
+    }
+}
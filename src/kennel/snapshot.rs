@@ -0,0 +1,30 @@
+use serde::Serialize;
+
+use crate::{
+    creature,
+    kennel::{Attractor, InteractionEvent, Treat},
+    math::Vec2,
+    sprite,
+};
+
+/**
+ * What a creature is up to at one tick.
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatureSnapshot {
+    pub id: String,
+    pub position: Vec2,
+    pub state: creature::State,
+    pub sprite_state: sprite::State,
+}
+
+/**
+ * The kennel at one tick, for logging and sending to clients.
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct Snapshot {
+    pub creatures: Vec<CreatureSnapshot>,
+    pub treats: Vec<Treat>,
+    pub attractors: Vec<Attractor>,
+    pub interactions: Vec<InteractionEvent>,
+}
@@ -0,0 +1,227 @@
+use std::collections::{BTreeMap, HashMap};
+
+use image::{Rgba, RgbaImage};
+use serde::Serialize;
+
+use crate::{Kennel, creature, kennel::Event, math::Vec2};
+
+/**
+ * How many cells along each side of the kennel a heatmap has by default.
+ */
+pub static DEFAULT_HEATMAP_RESOLUTION: usize = 32;
+
+/**
+ * How close to a wall a creature has to be to count as up against it, in kennel units.
+ */
+static WALL_CONTACT_DISTANCE: f64 = 0.001;
+
+/**
+ * Colours of the heatmap image from least to most visited.
+ */
+static HEATMAP_COLORS: [[f64; 3]; 4] = [
+    [20.0, 20.0, 40.0],
+    [200.0, 30.0, 30.0],
+    [250.0, 200.0, 40.0],
+    [255.0, 255.0, 255.0],
+];
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StateTicks {
+    pub idle: usize,
+    pub sleep: usize,
+    pub follow: usize,
+    pub flee: usize,
+}
+
+impl StateTicks {
+    fn add(&mut self, state: &creature::State) {
+        match state {
+            creature::State::Idle => self.idle += 1,
+            creature::State::Sleep => self.sleep += 1,
+            creature::State::Follow => self.follow += 1,
+            creature::State::Flee => self.flee += 1,
+        }
+    }
+}
+
+/**
+ * How often each cell of a grid over the kennel was visited, row by row from the top.
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct Heatmap {
+    pub resolution: usize,
+    pub counts: Vec<u64>,
+}
+
+impl Heatmap {
+    pub fn new(resolution: usize) -> Self {
+        Heatmap {
+            resolution,
+            counts: vec![0; resolution * resolution],
+        }
+    }
+
+    pub fn add(&mut self, position: &Vec2) {
+        let cell =
+            |value: f64| ((value * self.resolution as f64) as usize).min(self.resolution - 1);
+        let idx =
+            cell(position.y.clamp(0.0, 1.0)) * self.resolution + cell(position.x.clamp(0.0, 1.0));
+        self.counts[idx] += 1;
+    }
+
+    /**
+     * Draws the heatmap, with visits on a log scale so rarely visited cells still show.
+     */
+    pub fn to_image(&self, width: u32, height: u32) -> RgbaImage {
+        let max = self.counts.iter().max().copied().unwrap_or_default();
+        let scale = ((max + 1) as f64).ln();
+
+        RgbaImage::from_fn(width, height, |x, y| {
+            let column = (x as usize * self.resolution) / width as usize;
+            let row = (y as usize * self.resolution) / height as usize;
+            let count = self.counts[row * self.resolution + column];
+            let heat = if scale > 0.0 {
+                ((count + 1) as f64).ln() / scale
+            } else {
+                0.0
+            };
+
+            // blend between the two colours around the heat
+            let position = heat * (HEATMAP_COLORS.len() - 1) as f64;
+            let lower = (position.floor() as usize).min(HEATMAP_COLORS.len() - 2);
+            let t = position - lower as f64;
+            let channel = |idx: usize| {
+                (HEATMAP_COLORS[lower][idx] * (1.0 - t) + HEATMAP_COLORS[lower + 1][idx] * t) as u8
+            };
+            Rgba([channel(0), channel(1), channel(2), 255])
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatureStats {
+    pub ticks_in_state: StateTicks,
+    pub distance: f64,
+    pub collisions: usize,
+    pub wall_collisions: usize,
+    pub ticks_against_wall: usize,
+    pub heatmap: Heatmap,
+}
+
+impl CreatureStats {
+    fn new(resolution: usize) -> Self {
+        CreatureStats {
+            ticks_in_state: StateTicks::default(),
+            distance: 0.0,
+            collisions: 0,
+            wall_collisions: 0,
+            ticks_against_wall: 0,
+            heatmap: Heatmap::new(resolution),
+        }
+    }
+}
+
+/**
+ * What the creatures got up to over a run, recorded tick by tick.
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct Stats {
+    pub ticks: usize,
+    pub seconds: f64,
+    pub creatures: BTreeMap<String, CreatureStats>,
+    pub heatmap: Heatmap,
+    #[serde(skip)]
+    last_positions: HashMap<String, Vec2>,
+}
+
+impl Stats {
+    pub fn new(resolution: usize) -> Self {
+        Stats {
+            ticks: 0,
+            seconds: 0.0,
+            creatures: BTreeMap::new(),
+            heatmap: Heatmap::new(resolution.max(1)),
+            last_positions: HashMap::new(),
+        }
+    }
+
+    /**
+     * Sets where the creatures start out, without counting it as a tick.
+     */
+    pub fn record_start(&mut self, kennel: &Kennel) {
+        for creature in kennel.creatures() {
+            self.last_positions
+                .insert(creature.id.clone(), creature.position);
+        }
+    }
+
+    /**
+     * Records the tick that led to the kennel.
+     * Distances are measured from wherever each creature was at the last recorded tick,
+     * so creatures that wandered off to another room are forgotten until they come back.
+     */
+    pub fn record(&mut self, kennel: &Kennel) {
+        self.ticks += 1;
+        self.seconds += kennel.tick_duration().as_secs_f64();
+        self.last_positions
+            .retain(|id, _| kennel.get_creature(id).is_some());
+
+        let resolution = self.heatmap.resolution;
+        for creature in kennel.creatures() {
+            let stats = self
+                .creatures
+                .entry(creature.id.clone())
+                .or_insert_with(|| CreatureStats::new(resolution));
+
+            stats.ticks_in_state.add(&creature.creature_state);
+            stats.heatmap.add(&creature.position);
+            self.heatmap.add(&creature.position);
+
+            if let Some(last_position) = self.last_positions.get(&creature.id) {
+                stats.distance += (&creature.position - last_position).norm();
+            }
+            self.last_positions
+                .insert(creature.id.clone(), creature.position);
+
+            let Vec2 { x, y } = creature.position;
+            let gap = [x, y, 1.0 - x, 1.0 - y]
+                .into_iter()
+                .map(|distance| distance - creature.radius)
+                .fold(f64::INFINITY, f64::min);
+            if gap < WALL_CONTACT_DISTANCE {
+                stats.ticks_against_wall += 1;
+            }
+
+            let events = kennel
+                .events()
+                .iter()
+                .filter(|event| event.involves(&creature.id));
+            for event in events {
+                match event {
+                    Event::WallHit { .. } => stats.wall_collisions += 1,
+                    Event::Collision { .. } => stats.collisions += 1,
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heatmap() {
+        let mut heatmap = Heatmap::new(4);
+        heatmap.add(&Vec2::new(0.0, 0.0));
+        heatmap.add(&Vec2::new(0.99, 0.3));
+        heatmap.add(&Vec2::new(1.0, 0.3));
+        assert_eq!(heatmap.counts[0], 1);
+        assert_eq!(heatmap.counts[4 + 3], 2);
+
+        let image = heatmap.to_image(8, 8);
+        assert_eq!(image.get_pixel(7, 2).0, [255, 255, 255, 255]);
+        assert_eq!(image.get_pixel(7, 7).0, [20, 20, 40, 255]);
+    }
+}
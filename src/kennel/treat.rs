@@ -0,0 +1,37 @@
+use serde::Serialize;
+
+use crate::{creature::Creature, kennel::Marker, math::Vec2};
+
+/**
+ * How many ticks a treat lies around before it's gone, if nobody eats it first.
+ */
+pub static DEFAULT_TREAT_TICKS: usize = 60;
+
+/**
+ * Something dropped in the kennel that following creatures go for instead of each other.
+ */
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Treat {
+    #[serde(flatten)]
+    pub marker: Marker,
+}
+
+impl Treat {
+    pub fn is_eaten_by(&self, creature: &Creature) -> bool {
+        let distance = (&creature.position - &self.marker.position).squared_norm();
+        distance <= creature.radius * creature.radius
+    }
+}
+
+/**
+ * The position of the treat closest to `position`, if there are any.
+ */
+pub fn nearest<'a>(treats: &'a [Treat], position: &Vec2) -> Option<&'a Vec2> {
+    treats
+        .iter()
+        .map(|treat| &treat.marker.position)
+        .min_by(|a, b| {
+            let distance = |treat: &Vec2| (treat - position).squared_norm();
+            distance(a).total_cmp(&distance(b))
+        })
+}
@@ -1,10 +1,21 @@
+pub use auth::Owners;
 pub use image::ImageFormat;
-pub use kennel::Kennel;
+pub use kennel::{
+    ATTRACTOR_RANGE, Attractor, CreatureSnapshot, CreatureStats, DEFAULT_ATTRACTOR_TICKS,
+    DEFAULT_HEATMAP_RESOLUTION, DEFAULT_MAX_SUBSTEPS, DEFAULT_ROOM, DEFAULT_TREAT_TICKS,
+    DOORWAY_COOLDOWN_TICKS, Door, Doorway, Event, Heatmap, InteractionEvent, Kennel, KennelClub,
+    MAX_ATTRACTOR_STRENGTH, Marker, Migration, Reload, Simulation, Snapshot, StateTicks, Stats,
+    Treat,
+};
+pub use physics::{Opening, Wall};
 pub use rand;
+pub use render::{HitRegion, ImageOptions, Layer, Scene, Terminal, Theme, theme};
 pub use sprite::{Sprite, State};
 
+mod auth;
 pub mod creature;
 mod kennel;
 pub mod math;
 mod physics;
+mod render;
 mod sprite;
@@ -1,17 +1,8 @@
-use std::path::PathBuf;
-use std::{thread::sleep, time::Duration};
-
-use kennel_club::Kennel;
+mod cli;
 
 fn main() {
-    let mut rng = rand::rng();
-    let mut kennel = Kennel::load(&PathBuf::from("./data"), &mut rng).unwrap();
-
-    loop {
-        kennel.print();
-        kennel = kennel
-            .next(&mut rng)
-            .expect("Error creating the next kennel state");
-        sleep(Duration::from_secs(1));
+    if let Err(e) = cli::run(std::env::args().skip(1)) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
     }
 }
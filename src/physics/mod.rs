@@ -1,8 +1,7 @@
 pub use collidable::Collidable;
-pub use step::Step;
-
-#[cfg(test)]
-pub use step::DISTANCE_TOLERANCE;
+pub use opening::{Opening, Wall};
+pub use step::{Bound, DISTANCE_TOLERANCE, Step};
 
 mod collidable;
+mod opening;
 mod step;
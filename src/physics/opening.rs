@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{math::Vec2, physics::DISTANCE_TOLERANCE};
+
+/**
+ * A side of the unit square, with y pointing down.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Wall {
+    North,
+    East,
+    South,
+    West,
+}
+
+/**
+ * A gap in a wall, from `from` to `to` along it, that collidables walk into instead of bumping.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Opening {
+    pub wall: Wall,
+    pub from: f64,
+    pub to: f64,
+}
+
+impl Opening {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0 <= self.from && self.from < self.to && self.to <= 1.0) {
+            return Err(format!(
+                "Opening from {} to {} must be within the wall, with `from` before `to`",
+                self.from, self.to
+            ));
+        }
+        Ok(())
+    }
+
+    /**
+     * Whether a collidable of `radius` touching the opening's wall at `position` fits through.
+     */
+    pub fn fits(&self, position: &Vec2, radius: f64) -> bool {
+        let along = match self.wall {
+            Wall::North | Wall::South => position.x,
+            Wall::East | Wall::West => position.y,
+        };
+        self.from <= along - radius && along + radius <= self.to
+    }
+
+    /**
+     * Where a collidable of `radius` stands in the middle of the opening, just inside the walls.
+     */
+    pub fn entrance(&self, radius: f64) -> Vec2 {
+        let along = (self.from + self.to) / 2.0;
+        let (near, far) = (
+            radius + DISTANCE_TOLERANCE,
+            1.0 - radius - DISTANCE_TOLERANCE,
+        );
+        match self.wall {
+            Wall::North => Vec2::new(along, near),
+            Wall::East => Vec2::new(far, along),
+            Wall::South => Vec2::new(along, far),
+            Wall::West => Vec2::new(near, along),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fits() {
+        let opening = Opening {
+            wall: Wall::East,
+            from: 0.4,
+            to: 0.6,
+        };
+        assert!(opening.validate().is_ok());
+        assert!(opening.fits(&Vec2::new(0.9, 0.5), 0.1));
+        assert!(!opening.fits(&Vec2::new(0.9, 0.45), 0.1));
+        assert!(!opening.fits(&Vec2::new(0.9, 0.5), 0.2));
+
+        let entrance = opening.entrance(0.1);
+        assert!(entrance.x > 0.89 && entrance.x < 0.9);
+        assert_eq!(entrance.y, 0.5);
+
+        let backwards = Opening {
+            from: 0.6,
+            to: 0.4,
+            ..opening
+        };
+        assert!(backwards.validate().is_err());
+    }
+}
@@ -1,4 +1,19 @@
-use crate::{math::Vec2, physics::collidable::Collidable};
+use crate::{
+    math::Vec2,
+    physics::{Opening, Wall, collidable::Collidable},
+};
+
+/**
+ * What a step ran into at the edge of the unit square.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Wall,
+    /**
+     * The opening at this index, which the collidable fits through.
+     */
+    Opening(usize),
+}
 
 #[derive(Clone, Debug)]
 pub struct Step {
@@ -10,7 +25,7 @@ pub static DISTANCE_TOLERANCE: f64 = 0.000000000001;
 
 fn next_down_until<F: Fn(f64) -> bool>(t0: f64, f: F) -> f64 {
     let mut t = t0;
-    while !f(t0) {
+    while !f(t) {
         t = t.next_down();
     }
     t
@@ -18,7 +33,7 @@ fn next_down_until<F: Fn(f64) -> bool>(t0: f64, f: F) -> f64 {
 
 fn next_up_until<F: Fn(f64) -> bool>(t0: f64, f: F) -> f64 {
     let mut t = t0;
-    while !f(t0) {
+    while !f(t) {
         t = t.next_up();
     }
     t
@@ -49,38 +64,63 @@ impl Step {
         Self::new(self.collidable.clone(), t * &self.delta)
     }
 
-    pub fn unit_bound_collision_time(&self) -> Option<f64> {
+    /**
+     * When the step first touches the edge of the unit square, if it does,
+     * and whether that's a wall or one of the `openings` the collidable fits through.
+     */
+    pub fn unit_bound_collision_time(&self, openings: &[Opening]) -> Option<(f64, Bound)> {
         let current_position = &self.collidable.position;
 
         let final_position = current_position + &self.delta;
         let lower_bound = self.collidable.radius + DISTANCE_TOLERANCE;
         let upper_bound = 1.0 - self.collidable.radius - DISTANCE_TOLERANCE;
 
-        let t_x = if final_position.x < lower_bound {
-            (lower_bound - current_position.x) / self.delta.x
+        let x_crossing = if final_position.x < lower_bound {
+            Some((
+                (lower_bound - current_position.x) / self.delta.x,
+                Wall::West,
+            ))
         } else if final_position.x > upper_bound {
-            (upper_bound - current_position.x) / self.delta.x
+            Some((
+                (upper_bound - current_position.x) / self.delta.x,
+                Wall::East,
+            ))
         } else {
-            1.0
+            None
         };
 
-        let t_y = if final_position.y < lower_bound {
-            (lower_bound - current_position.y) / self.delta.y
+        let y_crossing = if final_position.y < lower_bound {
+            Some((
+                (lower_bound - current_position.y) / self.delta.y,
+                Wall::North,
+            ))
         } else if final_position.y > upper_bound {
-            (upper_bound - current_position.y) / self.delta.y
+            Some((
+                (upper_bound - current_position.y) / self.delta.y,
+                Wall::South,
+            ))
         } else {
-            1.0
+            None
         };
 
-        if t_x == 1.0 && t_y == 1.0 {
-            return None;
-        }
+        let (time, wall) = [x_crossing, y_crossing]
+            .into_iter()
+            .flatten()
+            .min_by(|a, b| a.0.total_cmp(&b.0))?;
+
+        let contact = current_position + &(time * &self.delta);
+        let bound = openings
+            .iter()
+            .position(|opening| {
+                opening.wall == wall && opening.fits(&contact, self.collidable.radius)
+            })
+            .map_or(Bound::Wall, Bound::Opening);
 
-        let t = next_down_until(f64::min(t_x, t_y), |t| {
+        let t = next_down_until(time, |t| {
             !self.lerp(t).resolve().is_out_of_unit_bounds() || t <= 0.0
         });
 
-        Some(t)
+        Some((t, bound))
     }
 
     pub fn steps_collision_time(step1: &Self, step2: &Self) -> Option<f64> {
@@ -159,3 +199,24 @@ impl From<Collidable> for Step {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_until() {
+        assert_eq!(next_down_until(1.0, |t| t < 1.0), 1.0f64.next_down());
+        assert_eq!(next_up_until(1.0, |t| t > 1.0), 1.0f64.next_up());
+        assert_eq!(next_down_until(0.5, |t| t < 1.0), 0.5);
+
+        // the step stops inside the walls, however the crossing time rounds
+        let step = Step::new(
+            Collidable::new(Vec2::new(0.5, 0.5), 0.1),
+            Vec2::new(0.7, 0.0),
+        );
+        let (t, bound) = step.unit_bound_collision_time(&[]).unwrap();
+        assert_eq!(bound, Bound::Wall);
+        assert!(!step.lerp(t).resolve().is_out_of_unit_bounds());
+    }
+}
@@ -0,0 +1,112 @@
+use image::{Rgba, RgbaImage};
+
+pub static GLYPH_WIDTH: u32 = 5;
+pub static GLYPH_HEIGHT: u32 = 7;
+
+/**
+ * Blank columns between glyphs, in font pixels.
+ */
+static GLYPH_SPACING: u32 = 1;
+
+/**
+ * A 5x7 bitmap font for printable ASCII, from ` ` to `~`.
+ * Each glyph is five columns, left to right, and bit n of a column is row n from the top.
+ */
+#[rustfmt::skip]
+static FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], [0x00, 0x00, 0x5f, 0x00, 0x00], [0x00, 0x07, 0x00, 0x07, 0x00], [0x14, 0x7f, 0x14, 0x7f, 0x14],
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], [0x23, 0x13, 0x08, 0x64, 0x62], [0x36, 0x49, 0x55, 0x22, 0x50], [0x00, 0x05, 0x03, 0x00, 0x00],
+    [0x00, 0x1c, 0x22, 0x41, 0x00], [0x00, 0x41, 0x22, 0x1c, 0x00], [0x08, 0x2a, 0x1c, 0x2a, 0x08], [0x08, 0x08, 0x3e, 0x08, 0x08],
+    [0x00, 0x50, 0x30, 0x00, 0x00], [0x08, 0x08, 0x08, 0x08, 0x08], [0x00, 0x60, 0x60, 0x00, 0x00], [0x20, 0x10, 0x08, 0x04, 0x02],
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], [0x00, 0x42, 0x7f, 0x40, 0x00], [0x42, 0x61, 0x51, 0x49, 0x46], [0x21, 0x41, 0x45, 0x4b, 0x31],
+    [0x18, 0x14, 0x12, 0x7f, 0x10], [0x27, 0x45, 0x45, 0x45, 0x39], [0x3c, 0x4a, 0x49, 0x49, 0x30], [0x01, 0x71, 0x09, 0x05, 0x03],
+    [0x36, 0x49, 0x49, 0x49, 0x36], [0x06, 0x49, 0x49, 0x29, 0x1e], [0x00, 0x36, 0x36, 0x00, 0x00], [0x00, 0x56, 0x36, 0x00, 0x00],
+    [0x08, 0x14, 0x22, 0x41, 0x00], [0x14, 0x14, 0x14, 0x14, 0x14], [0x00, 0x41, 0x22, 0x14, 0x08], [0x02, 0x01, 0x51, 0x09, 0x06],
+    [0x32, 0x49, 0x79, 0x41, 0x3e], [0x7e, 0x11, 0x11, 0x11, 0x7e], [0x7f, 0x49, 0x49, 0x49, 0x36], [0x3e, 0x41, 0x41, 0x41, 0x22],
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], [0x7f, 0x49, 0x49, 0x49, 0x41], [0x7f, 0x09, 0x09, 0x09, 0x01], [0x3e, 0x41, 0x49, 0x49, 0x7a],
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], [0x00, 0x41, 0x7f, 0x41, 0x00], [0x20, 0x40, 0x41, 0x3f, 0x01], [0x7f, 0x08, 0x14, 0x22, 0x41],
+    [0x7f, 0x40, 0x40, 0x40, 0x40], [0x7f, 0x02, 0x0c, 0x02, 0x7f], [0x7f, 0x04, 0x08, 0x10, 0x7f], [0x3e, 0x41, 0x41, 0x41, 0x3e],
+    [0x7f, 0x09, 0x09, 0x09, 0x06], [0x3e, 0x41, 0x51, 0x21, 0x5e], [0x7f, 0x09, 0x19, 0x29, 0x46], [0x46, 0x49, 0x49, 0x49, 0x31],
+    [0x01, 0x01, 0x7f, 0x01, 0x01], [0x3f, 0x40, 0x40, 0x40, 0x3f], [0x1f, 0x20, 0x40, 0x20, 0x1f], [0x3f, 0x40, 0x38, 0x40, 0x3f],
+    [0x63, 0x14, 0x08, 0x14, 0x63], [0x07, 0x08, 0x70, 0x08, 0x07], [0x61, 0x51, 0x49, 0x45, 0x43], [0x00, 0x7f, 0x41, 0x41, 0x00],
+    [0x02, 0x04, 0x08, 0x10, 0x20], [0x00, 0x41, 0x41, 0x7f, 0x00], [0x04, 0x02, 0x01, 0x02, 0x04], [0x40, 0x40, 0x40, 0x40, 0x40],
+    [0x00, 0x01, 0x02, 0x04, 0x00], [0x20, 0x54, 0x54, 0x54, 0x78], [0x7f, 0x48, 0x44, 0x44, 0x38], [0x38, 0x44, 0x44, 0x44, 0x20],
+    [0x38, 0x44, 0x44, 0x48, 0x7f], [0x38, 0x54, 0x54, 0x54, 0x18], [0x08, 0x7e, 0x09, 0x01, 0x02], [0x0c, 0x52, 0x52, 0x52, 0x3e],
+    [0x7f, 0x08, 0x04, 0x04, 0x78], [0x00, 0x44, 0x7d, 0x40, 0x00], [0x20, 0x40, 0x44, 0x3d, 0x00], [0x7f, 0x10, 0x28, 0x44, 0x00],
+    [0x00, 0x41, 0x7f, 0x40, 0x00], [0x7c, 0x04, 0x18, 0x04, 0x78], [0x7c, 0x08, 0x04, 0x04, 0x78], [0x38, 0x44, 0x44, 0x44, 0x38],
+    [0x7c, 0x14, 0x14, 0x14, 0x08], [0x08, 0x14, 0x14, 0x18, 0x7c], [0x7c, 0x08, 0x04, 0x04, 0x08], [0x48, 0x54, 0x54, 0x54, 0x20],
+    [0x04, 0x3f, 0x44, 0x40, 0x20], [0x3c, 0x40, 0x40, 0x20, 0x7c], [0x1c, 0x20, 0x40, 0x20, 0x1c], [0x3c, 0x40, 0x30, 0x40, 0x3c],
+    [0x44, 0x28, 0x10, 0x28, 0x44], [0x0c, 0x50, 0x50, 0x50, 0x3c], [0x44, 0x64, 0x54, 0x4c, 0x44], [0x00, 0x08, 0x36, 0x41, 0x00],
+    [0x00, 0x00, 0x7f, 0x00, 0x00], [0x00, 0x41, 0x36, 0x08, 0x00], [0x08, 0x04, 0x08, 0x10, 0x08],
+];
+
+/**
+ * The glyph for a character. Anything the font doesn't cover is drawn as `?`.
+ */
+fn glyph(character: char) -> &'static [u8; 5] {
+    let index = match character {
+        ' '..='~' => character as usize - ' ' as usize,
+        _ => '?' as usize - ' ' as usize,
+    };
+    &FONT[index]
+}
+
+/**
+ * The size of the text in canvas pixels, with every font pixel `scale` canvas pixels wide.
+ */
+pub fn text_size(text: &str, scale: u32) -> (u32, u32) {
+    let count = text.chars().count() as u32;
+    let width = (count * (GLYPH_WIDTH + GLYPH_SPACING)).saturating_sub(GLYPH_SPACING);
+    (width * scale, GLYPH_HEIGHT * scale)
+}
+
+/**
+ * Draws the text with its top left corner at `(x, y)`, clipping anything off canvas.
+ */
+pub fn draw_text(canvas: &mut RgbaImage, text: &str, x: i64, y: i64, scale: u32, color: &Rgba<u8>) {
+    let scale = scale as i64;
+    let advance = (GLYPH_WIDTH + GLYPH_SPACING) as i64 * scale;
+    for (idx, character) in text.chars().enumerate() {
+        let glyph_x = x + idx as i64 * advance;
+        for (column, bits) in glyph(character).iter().enumerate() {
+            for row in (0..GLYPH_HEIGHT).filter(|row| bits & (1 << row) != 0) {
+                let pixel_x = glyph_x + column as i64 * scale;
+                let pixel_y = y + row as i64 * scale;
+                fill_pixel(canvas, pixel_x, pixel_y, scale, color);
+            }
+        }
+    }
+}
+
+fn fill_pixel(canvas: &mut RgbaImage, x: i64, y: i64, scale: i64, color: &Rgba<u8>) {
+    for canvas_y in y.max(0)..(y + scale).min(canvas.height() as i64) {
+        for canvas_x in x.max(0)..(x + scale).min(canvas.width() as i64) {
+            let pixel = canvas.get_pixel_mut(canvas_x as u32, canvas_y as u32);
+            image::Pixel::blend(pixel, color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_text() {
+        let white = Rgba([255, 255, 255, 255]);
+        assert_eq!(text_size("Hi", 2), (22, 14));
+        assert_eq!(text_size("", 2), (0, 14));
+
+        let mut canvas = RgbaImage::new(22, 14);
+        draw_text(&mut canvas, "Hi", 0, 0, 2, &white);
+
+        // the left stroke of the H runs top to bottom, its middle is empty above the bar
+        assert_eq!(*canvas.get_pixel(0, 0), white);
+        assert_eq!(*canvas.get_pixel(1, 13), white);
+        assert_eq!(*canvas.get_pixel(4, 0), Rgba([0, 0, 0, 0]));
+        assert_eq!(*canvas.get_pixel(4, 6), white);
+
+        // text running off the canvas is clipped rather than panicking
+        draw_text(&mut canvas, "Hi", -3, 10, 2, &white);
+    }
+}
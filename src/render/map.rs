@@ -0,0 +1,147 @@
+use serde::Serialize;
+
+use crate::{
+    creature::Creature,
+    render::{Transform, raster::depth_sorted},
+};
+
+/**
+ * The pixels of a kennel image a creature's sprite covers, clipped to the canvas.
+ * Serializes to JSON for anything that wants to do its own hit testing.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HitRegion {
+    pub id: String,
+    pub display_name: String,
+    pub url: String,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl HitRegion {
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
+    }
+}
+
+/**
+ * The hit regions of the creatures in a `canvas_width` by `canvas_height` image.
+ * Regions are ordered front to back, so the first region containing a pixel
+ * is the creature drawn on top there. Creatures entirely off canvas have no region.
+ */
+pub fn hit_regions(
+    creatures: &[&Creature],
+    canvas_width: u32,
+    canvas_height: u32,
+) -> Vec<HitRegion> {
+    let transform = Transform::new(canvas_width, canvas_height);
+    let clip = |value: i64, max: u32| value.clamp(0, max as i64) as u32;
+
+    depth_sorted(creatures)
+        .into_iter()
+        .rev()
+        .filter_map(|creature| {
+            let (x, y, width, height) = transform.sprite_bounds(creature);
+            let (left, top) = (clip(x, canvas_width), clip(y, canvas_height));
+            let right = clip(x + width as i64, canvas_width);
+            let bottom = clip(y + height as i64, canvas_height);
+            if left == right || top == bottom {
+                return None;
+            }
+
+            Some(HitRegion {
+                id: creature.id.clone(),
+                display_name: creature.display_name.clone(),
+                url: creature.url.clone(),
+                x: left,
+                y: top,
+                width: right - left,
+                height: bottom - top,
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.chars()
+        .map(|character| match character {
+            '&' => "&amp;".to_string(),
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            '"' => "&quot;".to_string(),
+            '\'' => "&#39;".to_string(),
+            _ => character.to_string(),
+        })
+        .collect()
+}
+
+/**
+ * An HTML `<map>` linking each hit region to its creature's site.
+ * Use it with `<img src="..." usemap="#name">`.
+ */
+pub fn html_map(regions: &[HitRegion], name: &str) -> String {
+    let areas: String = regions
+        .iter()
+        .map(|region| {
+            format!(
+                "  <area shape=\"rect\" coords=\"{},{},{},{}\" href=\"{}\" alt=\"{}\" title=\"{}\">\n",
+                region.x,
+                region.y,
+                region.x + region.width,
+                region.y + region.height,
+                escape_html(&region.url),
+                escape_html(&region.display_name),
+                escape_html(&region.display_name),
+            )
+        })
+        .collect();
+
+    format!("<map name=\"{}\">\n{}</map>\n", escape_html(name), areas)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{creature::Metadata, math::Vec2};
+
+    use super::*;
+
+    #[test]
+    fn test_hit_regions() {
+        let creature = |x, y| {
+            let creature: Creature = Metadata::mock(0.1).into();
+            creature.set_position(Vec2::new(x, y))
+        };
+        let (back, front, clipped) = (creature(0.5, 0.5), creature(0.5, 0.6), creature(0.0, 0.0));
+
+        let regions = hit_regions(&[&back, &front, &clipped], 100, 100);
+        assert_eq!(regions.len(), 3);
+
+        // the front creature is first so it wins where they overlap
+        assert_eq!((regions[0].x, regions[0].y), (40, 50));
+        assert!(regions[0].contains(50, 55) && regions[1].contains(50, 55));
+
+        // the creature in the corner only keeps its on-canvas quarter
+        assert_eq!((regions[2].x, regions[2].y, regions[2].width), (0, 0, 10));
+    }
+
+    #[test]
+    fn test_html_map() {
+        let region = HitRegion {
+            id: "alt".to_string(),
+            display_name: "alt & co".to_string(),
+            url: "https://example.com/?a=1&b=2".to_string(),
+            x: 1,
+            y: 2,
+            width: 3,
+            height: 4,
+        };
+
+        let map = html_map(&[region], "kennel");
+        assert!(map.starts_with("<map name=\"kennel\">"));
+        assert!(map.contains("coords=\"1,2,4,6\""));
+        assert!(map.contains("href=\"https://example.com/?a=1&amp;b=2\""));
+        assert!(map.contains("alt=\"alt &amp; co\""));
+    }
+}
@@ -0,0 +1,15 @@
+pub use map::HitRegion;
+pub use raster::{ImageOptions, Layer};
+pub use scene::Scene;
+pub use terminal::Terminal;
+pub use theme::Theme;
+pub use transform::Transform;
+
+mod font;
+pub mod map;
+pub mod raster;
+mod scene;
+pub mod svg;
+mod terminal;
+pub mod theme;
+mod transform;
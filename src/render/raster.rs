@@ -0,0 +1,469 @@
+use std::io::{BufWriter, Cursor};
+
+use image::{DynamicImage, GenericImage, ImageFormat, Rgba, RgbaImage, imageops::overlay};
+
+use crate::{
+    Sprite,
+    creature::Creature,
+    kennel::Attractor,
+    math::Vec2,
+    render::{
+        Scene, Transform, font,
+        theme::{Day, DecorationLayer, Theme},
+    },
+};
+
+/**
+ * The layers of a kennel image, from back to front.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+    Background,
+    Shadows,
+    Creatures,
+    Labels,
+    Foreground,
+}
+
+/**
+ * Which layers to draw in a kennel image.
+ * Layers are always drawn in `Layer` order, whatever order they're listed in.
+ * What goes in the background and foreground comes from the kennel's theme.
+ */
+#[derive(Debug, Clone)]
+pub struct ImageOptions {
+    pub layers: Vec<Layer>,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        ImageOptions {
+            layers: vec![Layer::Background, Layer::Creatures, Layer::Foreground],
+        }
+    }
+}
+
+impl From<&Theme> for ImageOptions {
+    fn from(theme: &Theme) -> Self {
+        let mut options = ImageOptions::default();
+        if theme.shadows {
+            options.layers.push(Layer::Shadows);
+        }
+        if theme.labels {
+            options.layers.push(Layer::Labels);
+        }
+        options
+    }
+}
+
+/**
+ * Orders creatures back to front with the painter's algorithm:
+ * creatures whose collidable reaches lower in the kennel are drawn in front.
+ */
+pub fn depth_sorted<'a>(creatures: &[&'a Creature]) -> Vec<&'a Creature> {
+    let mut sorted = creatures.to_vec();
+    sorted.sort_by(|c1, c2| {
+        let bottom = |creature: &Creature| creature.position.y + creature.radius;
+        bottom(c1).total_cmp(&bottom(c2))
+    });
+    sorted
+}
+
+fn draw_stretched(canvas: &mut RgbaImage, sprite: &Sprite) {
+    let image = sprite.get_cached_resized_image(canvas.width(), canvas.height());
+    overlay(canvas, image.as_ref(), 0, 0);
+}
+
+/**
+ * Blends a rectangle onto the canvas, clipping anything off canvas.
+ */
+fn fill_rect(canvas: &mut RgbaImage, start: (i64, i64), end: (i64, i64), color: &Rgba<u8>) {
+    let clip = |value: i64, max: u32| value.clamp(0, max as i64) as u32;
+    for y in clip(start.1, canvas.height())..clip(end.1, canvas.height()) {
+        for x in clip(start.0, canvas.width())..clip(end.0, canvas.width()) {
+            let mut pixel = *canvas.get_pixel(x, y);
+            image::Pixel::blend(&mut pixel, color);
+            canvas.put_pixel(x, y, pixel);
+        }
+    }
+}
+
+/**
+ * The kennel's top left corner and side length on the canvas, in pixels.
+ */
+fn kennel_bounds(transform: &Transform) -> (i64, i64, i64) {
+    let origin = transform.world_to_canvas(&Vec2::zero());
+    (
+        origin.x.round() as i64,
+        origin.y.round() as i64,
+        transform.length_to_canvas(1.0).round() as i64,
+    )
+}
+
+fn draw_floor(canvas: &mut RgbaImage, transform: &Transform, theme: &Theme) {
+    let Some(floor) = &theme.floor else {
+        return;
+    };
+
+    let tile_length = transform.length_to_canvas(floor.tile_size).max(1.0);
+    let tile = floor
+        .texture
+        .get_cached_scaled_image(tile_length * floor.texture.scale_factor());
+    if tile.width() == 0 || tile.height() == 0 {
+        return;
+    }
+
+    // only tile the kennel itself, not the margins of a non-square canvas
+    let (x, y, length) = kennel_bounds(transform);
+    let mut kennel = canvas.sub_image(x as u32, y as u32, length as u32, length as u32);
+    for tile_y in (0..length).step_by(tile.height() as usize) {
+        for tile_x in (0..length).step_by(tile.width() as usize) {
+            overlay(&mut *kennel, tile.as_ref(), tile_x, tile_y);
+        }
+    }
+}
+
+fn draw_border(canvas: &mut RgbaImage, transform: &Transform, theme: &Theme) {
+    let Some(border) = &theme.border else {
+        return;
+    };
+
+    let width = transform.length_to_canvas(border.width).round().max(1.0) as i64;
+    let (x, y, length) = kennel_bounds(transform);
+    let color = &theme.palette.border.0;
+
+    fill_rect(canvas, (x, y), (x + length, y + width), color);
+    fill_rect(
+        canvas,
+        (x, y + length - width),
+        (x + length, y + length),
+        color,
+    );
+    fill_rect(
+        canvas,
+        (x, y + width),
+        (x + width, y + length - width),
+        color,
+    );
+    fill_rect(
+        canvas,
+        (x + length - width, y + width),
+        (x + length, y + length - width),
+        color,
+    );
+}
+
+fn draw_decorations(
+    canvas: &mut RgbaImage,
+    transform: &Transform,
+    theme: &Theme,
+    layer: &DecorationLayer,
+    day: &Day,
+) {
+    for decoration in theme.decorations_on(layer, day) {
+        let sprite = &decoration.sprite;
+        let image = sprite.get_cached_scaled_image(
+            transform.length_to_canvas(decoration.size) * sprite.scale_factor(),
+        );
+        let center = transform.world_to_canvas(&decoration.position);
+        let x = (center.x - image.width() as f64 / 2.0).round() as i64;
+        let y = (center.y - image.height() as f64 / 2.0).round() as i64;
+        overlay(canvas, image.as_ref(), x, y);
+    }
+}
+
+fn draw_background(canvas: &mut RgbaImage, transform: &Transform, theme: &Theme, day: &Day) {
+    if !theme.palette.background.is_transparent() {
+        let (width, height) = (canvas.width() as i64, canvas.height() as i64);
+        fill_rect(canvas, (0, 0), (width, height), &theme.palette.background.0);
+    }
+
+    if let Some(background) = &theme.background {
+        draw_stretched(canvas, background);
+    }
+
+    draw_floor(canvas, transform, theme);
+    draw_border(canvas, transform, theme);
+    draw_decorations(canvas, transform, theme, &DecorationLayer::Background, day);
+}
+
+/**
+ * Blends a filled ellipse onto the canvas, clipping anything off canvas.
+ */
+fn draw_ellipse(canvas: &mut RgbaImage, center: &Vec2, radii: &Vec2, color: &Rgba<u8>) {
+    if radii.x <= 0.0 || radii.y <= 0.0 {
+        return;
+    }
+
+    let clip = |value: f64, max: u32| value.clamp(0.0, max as f64) as u32;
+    let (x_start, x_end) = (
+        clip((center.x - radii.x).floor(), canvas.width()),
+        clip((center.x + radii.x).ceil(), canvas.width()),
+    );
+    let (y_start, y_end) = (
+        clip((center.y - radii.y).floor(), canvas.height()),
+        clip((center.y + radii.y).ceil(), canvas.height()),
+    );
+
+    for y in y_start..y_end {
+        for x in x_start..x_end {
+            let dx = (x as f64 + 0.5 - center.x) / radii.x;
+            let dy = (y as f64 + 0.5 - center.y) / radii.y;
+            if dx * dx + dy * dy <= 1.0 {
+                let mut pixel = *canvas.get_pixel(x, y);
+                image::Pixel::blend(&mut pixel, color);
+                canvas.put_pixel(x, y, pixel);
+            }
+        }
+    }
+}
+
+/**
+ * The center and radii of the shadow under a creature's feet, in kennel units.
+ */
+pub(crate) fn shadow_ellipse(creature: &Creature) -> (Vec2, Vec2) {
+    let feet = creature.position + Vec2::new(0.0, 0.8 * creature.radius);
+    let radii = Vec2::new(0.8 * creature.radius, 0.25 * creature.radius);
+    (feet, radii)
+}
+
+fn draw_shadow(
+    canvas: &mut RgbaImage,
+    transform: &Transform,
+    creature: &Creature,
+    color: &Rgba<u8>,
+) {
+    let (feet, radii) = shadow_ellipse(creature);
+    draw_ellipse(
+        canvas,
+        &transform.world_to_canvas(&feet),
+        &(transform.length_to_canvas(1.0) * &radii),
+        color,
+    );
+}
+
+fn draw_creature(canvas: &mut RgbaImage, transform: &Transform, creature: &Creature) {
+    let image = creature
+        .sprite()
+        .get_cached_scaled_image(transform.sprite_scale_factor(creature));
+
+    // sprites hanging off the canvas get clipped by the overlay
+    let (x, y) = transform.sprite_origin(creature, image.width(), image.height());
+    overlay(canvas, image.as_ref(), x, y);
+}
+
+/**
+ * How tall label text is, in kennel units.
+ */
+pub(crate) static LABEL_HEIGHT: f64 = 0.02;
+
+/**
+ * Writes the creature's display name centered under its sprite,
+ * over a drop shadow so it reads on any background.
+ */
+fn draw_label(canvas: &mut RgbaImage, transform: &Transform, creature: &Creature, theme: &Theme) {
+    let scale = (transform.length_to_canvas(LABEL_HEIGHT) / font::GLYPH_HEIGHT as f64)
+        .round()
+        .max(1.0) as u32;
+    let (text_width, _) = font::text_size(&creature.display_name, scale);
+
+    let (sprite_x, sprite_y, sprite_width, sprite_height) = transform.sprite_bounds(creature);
+    let x = sprite_x + (sprite_width as i64 - text_width as i64) / 2;
+    let y = sprite_y + sprite_height as i64 + scale as i64;
+
+    let shadow = &theme.palette.shadow.0;
+    let (offset, label) = (scale as i64, &theme.palette.label.0);
+    font::draw_text(
+        canvas,
+        &creature.display_name,
+        x + offset,
+        y + offset,
+        scale,
+        shadow,
+    );
+    font::draw_text(canvas, &creature.display_name, x, y, scale, label);
+}
+
+/**
+ * How big treats are drawn, in kennel units.
+ */
+pub(crate) static TREAT_RADIUS: f64 = 0.01;
+
+fn draw_treat(canvas: &mut RgbaImage, transform: &Transform, treat: &Vec2, theme: &Theme) {
+    let radius = transform.length_to_canvas(TREAT_RADIUS).max(0.5);
+    draw_ellipse(
+        canvas,
+        &transform.world_to_canvas(treat),
+        &Vec2::new(radius, radius),
+        &theme.palette.treat.0,
+    );
+}
+
+/**
+ * How big attractors and repellers are drawn, in kennel units.
+ */
+pub(crate) static ATTRACTOR_RADIUS: f64 = 0.02;
+
+fn draw_attractor(
+    canvas: &mut RgbaImage,
+    transform: &Transform,
+    attractor: &Attractor,
+    theme: &Theme,
+) {
+    let radius = transform.length_to_canvas(ATTRACTOR_RADIUS).max(0.5);
+    let color = if attractor.is_repeller() {
+        &theme.palette.repeller
+    } else {
+        &theme.palette.attractor
+    };
+    draw_ellipse(
+        canvas,
+        &transform.world_to_canvas(&attractor.marker.position),
+        &Vec2::new(radius, radius),
+        &color.0,
+    );
+}
+
+/**
+ * Composites the scene onto a canvas, layer by layer.
+ */
+pub fn render(
+    scene: &Scene,
+    canvas_width: u32,
+    canvas_height: u32,
+    options: &ImageOptions,
+) -> RgbaImage {
+    let mut canvas = RgbaImage::new(canvas_width, canvas_height);
+    let transform = Transform::new(canvas_width, canvas_height);
+    let (theme, day) = (scene.theme, &scene.day);
+    let creatures = depth_sorted(&scene.creatures);
+
+    let mut layers = options.layers.clone();
+    layers.sort();
+    layers.dedup();
+
+    for layer in layers.iter() {
+        match layer {
+            Layer::Background => draw_background(&mut canvas, &transform, theme, day),
+            Layer::Shadows => creatures.iter().for_each(|creature| {
+                draw_shadow(&mut canvas, &transform, creature, &theme.palette.shadow.0)
+            }),
+            Layer::Creatures => {
+                scene.attractors.iter().for_each(|attractor| {
+                    draw_attractor(&mut canvas, &transform, attractor, theme)
+                });
+                scene
+                    .treats
+                    .iter()
+                    .for_each(|treat| draw_treat(&mut canvas, &transform, treat, theme));
+                creatures
+                    .iter()
+                    .for_each(|creature| draw_creature(&mut canvas, &transform, creature));
+            }
+            Layer::Labels => creatures
+                .iter()
+                .for_each(|creature| draw_label(&mut canvas, &transform, creature, theme)),
+            Layer::Foreground => draw_decorations(
+                &mut canvas,
+                &transform,
+                theme,
+                &DecorationLayer::Foreground,
+                day,
+            ),
+        }
+    }
+
+    canvas
+}
+
+pub fn encode(canvas: RgbaImage, image_format: ImageFormat) -> Result<Vec<u8>, String> {
+    let canvas = DynamicImage::from(canvas);
+    let mut image_buffer = BufWriter::new(Cursor::new(Vec::new()));
+    canvas
+        .write_to(&mut image_buffer, image_format)
+        .map_err(|_| "Error writing kennel image")?;
+
+    let bytes = image_buffer
+        .into_inner()
+        .map(Cursor::into_inner)
+        .map_err(|_| "Error writing kennel image to bytes")?;
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::creature::Metadata;
+
+    use super::*;
+
+    #[test]
+    fn test_depth_sorted() {
+        let mock = |y: f64, radius: f64| {
+            let creature: Creature = Metadata::mock(radius).into();
+            creature.set_position(Vec2::new(0.5, y))
+        };
+
+        let front = mock(0.8, 0.1);
+        let back = mock(0.2, 0.1);
+        // further up, but big enough to reach below `back`
+        let big = mock(0.15, 0.2);
+
+        let sorted = depth_sorted(&[&front, &big, &back]);
+        let ids: Vec<_> = sorted.iter().map(|creature| &creature.id).collect();
+        assert_eq!(ids, vec![&back.id, &big.id, &front.id]);
+    }
+
+    #[test]
+    fn test_draw_border() {
+        let theme = Theme {
+            border: Some(crate::render::theme::Border { width: 0.1 }),
+            ..Theme::default()
+        };
+
+        // a 10px wide kennel centered in a 20px wide canvas
+        let mut canvas = RgbaImage::new(20, 10);
+        draw_border(&mut canvas, &Transform::new(20, 10), &theme);
+
+        let border = theme.palette.border.0;
+        assert_eq!(*canvas.get_pixel(5, 0), border);
+        assert_eq!(*canvas.get_pixel(14, 9), border);
+        assert_eq!(*canvas.get_pixel(10, 5), Rgba([0, 0, 0, 0]));
+        assert_eq!(*canvas.get_pixel(2, 0), Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn test_draw_stretched() {
+        let mut image = RgbaImage::new(2, 1);
+        image.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        let background = Sprite::from_image(image.into(), ImageFormat::Png);
+
+        let mut canvas = RgbaImage::new(4, 2);
+        draw_stretched(&mut canvas, &background);
+        assert_eq!(*canvas.get_pixel(1, 1), Rgba([255, 0, 0, 255]));
+        assert_eq!(*canvas.get_pixel(2, 0), Rgba([0, 0, 0, 0]));
+
+        // the next render at the same size reuses the stretched background
+        assert!(Arc::ptr_eq(
+            &background.get_cached_resized_image(4, 2),
+            &background.get_cached_resized_image(4, 2)
+        ));
+    }
+
+    #[test]
+    fn test_draw_ellipse_clips() {
+        let mut canvas = RgbaImage::new(4, 4);
+        let color = Rgba([0, 0, 0, 255]);
+        draw_ellipse(
+            &mut canvas,
+            &Vec2::new(0.0, 0.0),
+            &Vec2::new(2.0, 2.0),
+            &color,
+        );
+
+        assert_eq!(*canvas.get_pixel(0, 0), color);
+        assert_eq!(*canvas.get_pixel(3, 3), Rgba([0, 0, 0, 0]));
+    }
+}
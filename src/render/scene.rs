@@ -0,0 +1,34 @@
+use crate::{
+    creature::Creature,
+    kennel::Attractor,
+    math::Vec2,
+    render::theme::{Day, Theme},
+};
+
+/**
+ * Everything a renderer draws: the creatures, what's lying around the kennel,
+ * and the theme as it looks on `day`.
+ */
+#[derive(Debug, Clone)]
+pub struct Scene<'a> {
+    pub creatures: Vec<&'a Creature>,
+    pub treats: Vec<Vec2>,
+    pub attractors: Vec<Attractor>,
+    pub theme: &'a Theme,
+    pub day: Day,
+}
+
+impl<'a> Scene<'a> {
+    /**
+     * Just the creatures, with the default theme and nothing else in the kennel.
+     */
+    pub fn new(creatures: Vec<&'a Creature>, theme: &'a Theme) -> Self {
+        Scene {
+            creatures,
+            treats: vec![],
+            attractors: vec![],
+            theme,
+            day: Day::today(),
+        }
+    }
+}
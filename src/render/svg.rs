@@ -0,0 +1,375 @@
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+
+use crate::{
+    Sprite,
+    creature::Creature,
+    kennel::Attractor,
+    math::Vec2,
+    render::{
+        ImageOptions, Layer, Scene, Transform,
+        map::escape_html,
+        raster::{ATTRACTOR_RADIUS, LABEL_HEIGHT, TREAT_RADIUS, depth_sorted, shadow_ellipse},
+        theme::{Color, Day, DecorationLayer, Theme},
+    },
+};
+
+/**
+ * The side length of the document's view box.
+ * The document scales to whatever it's embedded in, this only sets the precision of coordinates.
+ */
+static VIEW_BOX_SIZE: u32 = 1000;
+
+/**
+ * A `fill` or `stroke` attribute with its opacity, since not every viewer reads `#rrggbbaa`.
+ */
+fn paint(attribute: &str, color: &Color) -> String {
+    let [r, g, b, a] = color.0.0;
+    format!(
+        "{attribute}=\"#{:02x}{:02x}{:02x}\" {attribute}-opacity=\"{:.3}\"",
+        r,
+        g,
+        b,
+        a as f64 / 255.0
+    )
+}
+
+fn data_uri(sprite: &Sprite) -> Result<String, String> {
+    Ok(format!(
+        "data:{};base64,{}",
+        sprite.format().to_mime_type(),
+        STANDARD.encode(sprite.bytes()?)
+    ))
+}
+
+/**
+ * An `<image>` of the sprite with its longest side `size` long, centered on `center`.
+ */
+fn image(svg: &mut String, sprite: &Sprite, center: &Vec2, size: f64) -> Result<(), String> {
+    let (width, height) = sprite.scaled_size(size * sprite.scale_factor());
+    let _ = writeln!(
+        svg,
+        "<image href=\"{}\" x=\"{:.2}\" y=\"{:.2}\" width=\"{}\" height=\"{}\" preserveAspectRatio=\"none\"/>",
+        data_uri(sprite)?,
+        center.x - width as f64 / 2.0,
+        center.y - height as f64 / 2.0,
+        width,
+        height,
+    );
+    Ok(())
+}
+
+static NEXT_DOCUMENT_ID: AtomicU64 = AtomicU64::new(0);
+
+/**
+ * A prefix for the ids in a document, so documents inlined into the same page
+ * don't pick up each other's patterns.
+ */
+fn next_document_id() -> String {
+    format!(
+        "kennel-{}",
+        NEXT_DOCUMENT_ID.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+fn background(
+    svg: &mut String,
+    id: &str,
+    transform: &Transform,
+    theme: &Theme,
+    day: &Day,
+) -> Result<(), String> {
+    if !theme.palette.background.is_transparent() {
+        let _ = writeln!(
+            svg,
+            "<rect width=\"100%\" height=\"100%\" {}/>",
+            paint("fill", &theme.palette.background)
+        );
+    }
+
+    if let Some(background) = &theme.background {
+        let _ = writeln!(
+            svg,
+            "<image href=\"{}\" width=\"100%\" height=\"100%\" preserveAspectRatio=\"none\"/>",
+            data_uri(background)?
+        );
+    }
+
+    let size = transform.length_to_canvas(1.0);
+    if let Some(floor) = &theme.floor {
+        let (width, height) = floor.texture.scaled_size(
+            transform.length_to_canvas(floor.tile_size) * floor.texture.scale_factor(),
+        );
+        let _ = writeln!(
+            svg,
+            "<defs><pattern id=\"{id}-floor\" patternUnits=\"userSpaceOnUse\" width=\"{w}\" height=\"{h}\">\
+             <image href=\"{}\" width=\"{w}\" height=\"{h}\" preserveAspectRatio=\"none\"/></pattern></defs>",
+            data_uri(&floor.texture)?,
+            w = width,
+            h = height,
+        );
+        let _ = writeln!(
+            svg,
+            "<rect width=\"{size}\" height=\"{size}\" fill=\"url(#{id}-floor)\"/>"
+        );
+    }
+
+    if let Some(border) = &theme.border {
+        // the stroke is centered on the outline, so inset it to keep it inside the kennel
+        let width = transform.length_to_canvas(border.width);
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{inset:.2}\" y=\"{inset:.2}\" width=\"{side:.2}\" height=\"{side:.2}\" fill=\"none\" {} stroke-width=\"{width:.2}\"/>",
+            paint("stroke", &theme.palette.border),
+            inset = width / 2.0,
+            side = size - width,
+        );
+    }
+
+    decorations(svg, transform, theme, &DecorationLayer::Background, day)
+}
+
+fn decorations(
+    svg: &mut String,
+    transform: &Transform,
+    theme: &Theme,
+    layer: &DecorationLayer,
+    day: &Day,
+) -> Result<(), String> {
+    for decoration in theme.decorations_on(layer, day) {
+        image(
+            svg,
+            &decoration.sprite,
+            &transform.world_to_canvas(&decoration.position),
+            transform.length_to_canvas(decoration.size),
+        )?;
+    }
+    Ok(())
+}
+
+fn shadow(svg: &mut String, transform: &Transform, creature: &Creature, color: &Color) {
+    let (feet, radii) = shadow_ellipse(creature);
+    let center = transform.world_to_canvas(&feet);
+    let _ = writeln!(
+        svg,
+        "<ellipse cx=\"{:.2}\" cy=\"{:.2}\" rx=\"{:.2}\" ry=\"{:.2}\" {}/>",
+        center.x,
+        center.y,
+        transform.length_to_canvas(radii.x),
+        transform.length_to_canvas(radii.y),
+        paint("fill", color),
+    );
+}
+
+fn treat(svg: &mut String, transform: &Transform, position: &Vec2, theme: &Theme) {
+    let center = transform.world_to_canvas(position);
+    let _ = writeln!(
+        svg,
+        "<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"{:.2}\" {}/>",
+        center.x,
+        center.y,
+        transform.length_to_canvas(TREAT_RADIUS),
+        paint("fill", &theme.palette.treat),
+    );
+}
+
+fn attractor(svg: &mut String, transform: &Transform, attractor: &Attractor, theme: &Theme) {
+    let center = transform.world_to_canvas(&attractor.marker.position);
+    let color = if attractor.is_repeller() {
+        &theme.palette.repeller
+    } else {
+        &theme.palette.attractor
+    };
+    let _ = writeln!(
+        svg,
+        "<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"{:.2}\" {}/>",
+        center.x,
+        center.y,
+        transform.length_to_canvas(ATTRACTOR_RADIUS),
+        paint("fill", color),
+    );
+}
+
+/**
+ * The creature's sprite, linked to its site and titled with its name.
+ */
+fn creature(svg: &mut String, transform: &Transform, creature: &Creature) -> Result<(), String> {
+    let (x, y, width, height) = transform.sprite_bounds(creature);
+    let _ = writeln!(
+        svg,
+        "<a href=\"{}\"><title>{}</title><image href=\"{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" preserveAspectRatio=\"none\"/></a>",
+        escape_html(&creature.url),
+        escape_html(&creature.display_name),
+        data_uri(creature.sprite())?,
+        x,
+        y,
+        width,
+        height,
+    );
+    Ok(())
+}
+
+fn label(svg: &mut String, transform: &Transform, creature: &Creature, theme: &Theme) {
+    let (x, y, width, height) = transform.sprite_bounds(creature);
+    let font_size = transform.length_to_canvas(LABEL_HEIGHT);
+    let _ = writeln!(
+        svg,
+        "<text x=\"{:.2}\" y=\"{:.2}\" font-family=\"monospace\" font-size=\"{:.2}\" text-anchor=\"middle\" dominant-baseline=\"hanging\" {}>{}</text>",
+        x as f64 + width as f64 / 2.0,
+        y as f64 + height as f64 + font_size / 4.0,
+        font_size,
+        paint("fill", &theme.palette.label),
+        escape_html(&creature.display_name),
+    );
+}
+
+/**
+ * Renders the kennel as an SVG document that scales to fit whatever it's embedded in.
+ * Sprites are embedded, so the document stands on its own.
+ */
+pub fn render(scene: &Scene, options: &ImageOptions) -> Result<String, String> {
+    let transform = Transform::new(VIEW_BOX_SIZE, VIEW_BOX_SIZE);
+    let id = next_document_id();
+    let (theme, day) = (scene.theme, &scene.day);
+    let creatures = depth_sorted(&scene.creatures);
+
+    let mut layers = options.layers.clone();
+    layers.sort();
+    layers.dedup();
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {size} {size}\" style=\"image-rendering: pixelated\">\n",
+        size = VIEW_BOX_SIZE
+    );
+    for layer in layers.iter() {
+        match layer {
+            Layer::Background => background(&mut svg, &id, &transform, theme, day)?,
+            Layer::Shadows => creatures
+                .iter()
+                .for_each(|creature| shadow(&mut svg, &transform, creature, &theme.palette.shadow)),
+            Layer::Creatures => {
+                scene
+                    .attractors
+                    .iter()
+                    .for_each(|a| attractor(&mut svg, &transform, a, theme));
+                scene
+                    .treats
+                    .iter()
+                    .for_each(|position| treat(&mut svg, &transform, position, theme));
+                for c in creatures.iter() {
+                    creature(&mut svg, &transform, c)?;
+                }
+            }
+            Layer::Labels => creatures
+                .iter()
+                .for_each(|creature| label(&mut svg, &transform, creature, theme)),
+            Layer::Foreground => decorations(
+                &mut svg,
+                &transform,
+                theme,
+                &DecorationLayer::Foreground,
+                day,
+            )?,
+        }
+    }
+    svg.push_str("</svg>\n");
+
+    Ok(svg)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{ImageFormat, RgbaImage};
+
+    use crate::{
+        creature::Metadata,
+        kennel::Marker,
+        render::theme::{Decoration, Floor},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut creature: Creature = Metadata::mock(0.1).into();
+        creature.display_name = "<alt>".to_string();
+        creature.url = "https://example.com".to_string();
+        let creature = creature.set_position(Vec2::new(0.5, 0.5));
+
+        let options = ImageOptions {
+            layers: vec![Layer::Creatures, Layer::Labels],
+        };
+        let theme = Theme::default();
+        let scene = Scene {
+            attractors: vec![Attractor {
+                marker: Marker::new("Attractor", Vec2::new(0.25, 0.25), 1).unwrap(),
+                strength: -1.0,
+            }],
+            ..Scene::new(vec![&creature], &theme)
+        };
+        let svg = render(&scene, &options).expect("Sprites must encode");
+
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains("<a href=\"https://example.com\"><title>&lt;alt&gt;</title>"));
+        assert!(svg.contains("x=\"400\" y=\"400\" width=\"200\" height=\"200\""));
+        assert!(svg.contains("data:image/png;base64,"));
+        assert!(svg.contains("<circle cx=\"250.00\" cy=\"250.00\" r=\"20.00\" fill=\"#d65656\""));
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+
+    #[test]
+    fn test_floor_ids_are_unique() {
+        let theme = Theme {
+            floor: Some(Floor {
+                texture: Sprite::from_image(RgbaImage::new(1, 1).into(), ImageFormat::Png),
+                tile_size: 0.1,
+            }),
+            ..Theme::default()
+        };
+        let options = ImageOptions {
+            layers: vec![Layer::Background],
+        };
+        let pattern_id = |svg: &str| {
+            let start = svg.find("<pattern id=\"").expect("Floor must be drawn") + 13;
+            let end = start + svg[start..].find('"').unwrap();
+            svg[start..end].to_string()
+        };
+
+        // two kennels inlined into one page must not share a pattern
+        let first = render(&Scene::new(vec![], &theme), &options).expect("Sprites must encode");
+        let second = render(&Scene::new(vec![], &theme), &options).expect("Sprites must encode");
+        let id = pattern_id(&first);
+        assert!(first.contains(&format!("fill=\"url(#{})\"", id)));
+        assert_ne!(id, pattern_id(&second));
+    }
+
+    #[test]
+    fn test_foreground_covers_labels() {
+        let creature: Creature = Metadata::mock(0.1).into();
+        let creature = creature.set_position(Vec2::new(0.5, 0.5));
+        let theme = Theme {
+            decorations: vec![Decoration {
+                sprite: Sprite::from_image(RgbaImage::new(1, 1).into(), ImageFormat::Png),
+                position: Vec2::new(0.5, 0.65),
+                size: 0.2,
+                layer: DecorationLayer::Foreground,
+                season: None,
+            }],
+            ..Theme::default()
+        };
+
+        let options = ImageOptions {
+            layers: vec![Layer::Foreground, Layer::Labels],
+        };
+        let svg =
+            render(&Scene::new(vec![&creature], &theme), &options).expect("Sprites must encode");
+
+        let label = svg.find("<text").expect("Label must be drawn");
+        let decoration = svg.find("<image").expect("Decoration must be drawn");
+        assert!(label < decoration);
+    }
+}
@@ -0,0 +1,324 @@
+use std::io::{Stdout, Write};
+
+use image::{Rgba, RgbaImage};
+use termion::{
+    color, cursor,
+    cursor::HideCursor,
+    screen::{AlternateScreen, IntoAlternateScreen},
+    terminal_size,
+};
+
+use crate::{
+    math::Vec2,
+    render::{ImageOptions, Scene, Transform, raster},
+};
+
+/**
+ * Pixels at least this opaque are drawn, anything fainter shows the terminal's own background.
+ */
+static ALPHA_THRESHOLD: u8 = 128;
+
+/**
+ * The size used when the terminal can't be asked for its own.
+ */
+static FALLBACK_SIZE: (u16, u16) = (80, 24);
+
+/**
+ * One terminal cell, showing two pixels stacked on top of each other with half blocks.
+ * `None` is a transparent pixel.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cell {
+    top: Option<[u8; 3]>,
+    bottom: Option<[u8; 3]>,
+}
+
+fn opaque(pixel: &Rgba<u8>) -> Option<[u8; 3]> {
+    let Rgba([r, g, b, a]) = *pixel;
+    (a >= ALPHA_THRESHOLD).then_some([r, g, b])
+}
+
+/**
+ * A whole screen of cells, row by row.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Frame {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+}
+
+impl Frame {
+    /**
+     * Packs an image twice as tall as the frame into cells.
+     */
+    fn from_image(image: &RgbaImage, width: u16, height: u16) -> Self {
+        let pixel = |x: u32, y: u32| image.get_pixel_checked(x, y).and_then(opaque);
+
+        let cells = (0..height as u32)
+            .flat_map(|row| {
+                (0..width as u32).map(move |column| Cell {
+                    top: pixel(column, 2 * row),
+                    bottom: pixel(column, 2 * row + 1),
+                })
+            })
+            .collect();
+
+        Frame {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    /**
+     * The column and row of the cell at `idx`, counting in `usize` since big frames
+     * have more cells than a `u16` holds.
+     */
+    fn cell_position(&self, idx: usize) -> (u16, u16) {
+        let width = self.width as usize;
+        ((idx % width) as u16, (idx / width) as u16)
+    }
+}
+
+/**
+ * The colours last written, so runs of same coloured cells don't repeat escape codes.
+ */
+#[derive(Default)]
+struct Pen {
+    foreground: Option<Option<[u8; 3]>>,
+    background: Option<Option<[u8; 3]>>,
+}
+
+impl Pen {
+    fn write_cell<W: Write>(&mut self, out: &mut W, cell: &Cell) -> std::io::Result<()> {
+        // transparent halves are left to the terminal's background,
+        // so a cell drawn with only its bottom half uses `▄` instead
+        let (foreground, background, character) = match *cell {
+            Cell {
+                top: None,
+                bottom: None,
+            } => (self.foreground.flatten(), None, ' '),
+            Cell { top: None, bottom } => (bottom, None, '▄'),
+            Cell { top, bottom } => (top, bottom, '▀'),
+        };
+
+        if self.foreground != Some(foreground) {
+            match foreground {
+                Some([r, g, b]) => write!(out, "{}", color::Fg(color::Rgb(r, g, b)))?,
+                None => write!(out, "{}", color::Fg(color::Reset))?,
+            }
+            self.foreground = Some(foreground);
+        }
+        if self.background != Some(background) {
+            match background {
+                Some([r, g, b]) => write!(out, "{}", color::Bg(color::Rgb(r, g, b)))?,
+                None => write!(out, "{}", color::Bg(color::Reset))?,
+            }
+            self.background = Some(background);
+        }
+
+        write!(out, "{}", character)
+    }
+}
+
+/**
+ * Draws kennels to a terminal with half-block characters in 24-bit colour,
+ * two kennel pixels to a cell. Only cells that changed since the last draw are written.
+ */
+pub struct Terminal<W: Write> {
+    out: W,
+    size: Option<(u16, u16)>,
+    previous: Option<Frame>,
+}
+
+impl Terminal<HideCursor<AlternateScreen<Stdout>>> {
+    /**
+     * A terminal drawing to stdout's alternate screen, sized to fit the terminal.
+     * The main screen and cursor come back when it's dropped.
+     */
+    pub fn stdout() -> Result<Self, String> {
+        let screen = std::io::stdout()
+            .into_alternate_screen()
+            .map_err(|e| format!("Unable to switch to the alternate screen. {}", e))?;
+        Ok(Terminal::new(HideCursor::from(screen)))
+    }
+}
+
+impl<W: Write> Terminal<W> {
+    /**
+     * A terminal drawing to `out`, sized to fit the terminal it's running in.
+     */
+    pub fn new(out: W) -> Self {
+        Terminal {
+            out,
+            size: None,
+            previous: None,
+        }
+    }
+
+    /**
+     * A terminal drawing to `out` at a fixed number of columns and rows.
+     */
+    pub fn with_size(out: W, columns: u16, rows: u16) -> Self {
+        Terminal {
+            out,
+            size: Some((columns, rows)),
+            previous: None,
+        }
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        self.size
+            .unwrap_or_else(|| terminal_size().unwrap_or(FALLBACK_SIZE))
+    }
+
+    /**
+     * Draws at a fixed number of columns and rows from now on.
+     */
+    pub fn resize(&mut self, columns: u16, rows: u16) {
+        self.size = Some((columns, rows));
+    }
+
+    /**
+     * The kennel position under a cell, with one-based coordinates like termion's.
+     */
+    pub fn cell_to_world(&self, column: u16, row: u16) -> Vec2 {
+        let (columns, rows) = self.size();
+        let transform = Transform::new(columns as u32, 2 * rows as u32);
+
+        // cells are two pixels tall, so aim for the middle of the cell
+        let canvas_position = Vec2::new(column as f64 - 0.5, 2.0 * row as f64 - 1.0);
+        transform.canvas_to_world(&canvas_position)
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.out
+    }
+
+    /**
+     * The output, for writing around the kennel.
+     * Anything written over the kennel's cells isn't noticed until `invalidate` is called.
+     */
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.out
+    }
+
+    /**
+     * Forgets what's on screen so the next draw redraws every cell,
+     * e.g. after something else wrote to the terminal.
+     */
+    pub fn invalidate(&mut self) {
+        self.previous = None;
+    }
+
+    pub fn draw(&mut self, scene: &Scene, options: &ImageOptions) -> Result<(), String> {
+        let (columns, rows) = self.size();
+        let image = raster::render(scene, columns as u32, 2 * rows as u32, options);
+        let frame = Frame::from_image(&image, columns, rows);
+
+        self.write_frame(&frame)
+            .map_err(|e| format!("Unable to draw to the terminal. {}", e))?;
+        self.previous = Some(frame);
+
+        Ok(())
+    }
+
+    fn write_frame(&mut self, frame: &Frame) -> std::io::Result<()> {
+        // a resized terminal has nothing worth keeping on screen, so start from a cleared one
+        let blank = Cell {
+            top: None,
+            bottom: None,
+        };
+        let previous = self
+            .previous
+            .as_ref()
+            .filter(|previous| previous.width == frame.width && previous.height == frame.height);
+        if previous.is_none() {
+            write!(
+                self.out,
+                "{}{}{}",
+                color::Fg(color::Reset),
+                color::Bg(color::Reset),
+                termion::clear::All
+            )?;
+        }
+
+        let mut pen = Pen::default();
+        let mut cursor_at = None;
+        for (idx, cell) in frame.cells.iter().enumerate() {
+            let shown = previous.map_or(&blank, |previous| &previous.cells[idx]);
+            if shown == cell {
+                continue;
+            }
+
+            // writing a cell moves the cursor along, so only runs of changes need a jump
+            if cursor_at != Some(idx) {
+                let (column, row) = frame.cell_position(idx);
+                write!(self.out, "{}", cursor::Goto(column + 1, row + 1))?;
+            }
+            pen.write_cell(&mut self.out, cell)?;
+            cursor_at = Some(idx + 1).filter(|next| next % frame.width as usize != 0);
+        }
+
+        write!(
+            self.out,
+            "{}{}",
+            color::Fg(color::Reset),
+            color::Bg(color::Reset)
+        )?;
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_image() {
+        let mut image = RgbaImage::new(2, 3);
+        image.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        image.put_pixel(1, 2, Rgba([0, 0, 255, 255]));
+        image.put_pixel(1, 1, Rgba([0, 255, 0, 10]));
+
+        let frame = Frame::from_image(&image, 2, 2);
+        assert_eq!(
+            frame.cells[0],
+            Cell {
+                top: Some([255, 0, 0]),
+                bottom: None
+            }
+        );
+        assert_eq!(frame.cells[1].bottom, None);
+        assert_eq!(frame.cells[3].top, Some([0, 0, 255]));
+
+        // the odd row out has nothing under it
+        assert_eq!(frame.cells[3].bottom, None);
+    }
+
+    #[test]
+    fn test_cell_position() {
+        let frame = Frame::from_image(&RgbaImage::new(0, 0), 300, 300);
+        assert_eq!(frame.cell_position(301), (1, 1));
+        assert_eq!(frame.cell_position(300 * 300 - 1), (299, 299));
+    }
+
+    #[test]
+    fn test_redraw_only_changes() {
+        let mut terminal = Terminal::with_size(Vec::new(), 4, 2);
+        let blank = Frame::from_image(&RgbaImage::new(4, 4), 4, 2);
+        terminal.write_frame(&blank).unwrap();
+        terminal.previous = Some(blank.clone());
+        let full_length = terminal.get_ref().len();
+
+        let mut changed = blank.clone();
+        changed.cells[5].top = Some([1, 2, 3]);
+        terminal.write_frame(&changed).unwrap();
+        let written = String::from_utf8(terminal.get_ref()[full_length..].to_vec()).unwrap();
+
+        assert_eq!(written.matches('▀').count(), 1);
+        assert!(written.contains(&cursor::Goto(2, 2).to_string()));
+        assert!(!written.contains(&termion::clear::All.to_string()));
+    }
+}
@@ -0,0 +1,381 @@
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use image::Rgba;
+use serde::Deserialize;
+
+use crate::{Sprite, math::Vec2};
+
+/**
+ * An RGBA colour, written as `#rrggbb` or `#rrggbbaa` in JSON.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Color(pub Rgba<u8>);
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let hex = value
+            .strip_prefix('#')
+            .filter(|hex| (hex.len() == 6 || hex.len() == 8) && hex.is_ascii())
+            .ok_or(format!(
+                "Colour {} must look like #rrggbb or #rrggbbaa",
+                value
+            ))?;
+
+        let channel = |idx: usize| {
+            hex.get(idx..idx + 2)
+                .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+                .ok_or(format!("Colour {} is not valid hex", value))
+        };
+
+        let alpha = if hex.len() == 8 { channel(6)? } else { 255 };
+        Ok(Color(Rgba([channel(0)?, channel(2)?, channel(4)?, alpha])))
+    }
+}
+
+impl Color {
+    pub fn is_transparent(&self) -> bool {
+        self.0[3] == 0
+    }
+
+    pub fn to_hex(self) -> String {
+        let Rgba([r, g, b, a]) = self.0;
+        format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct Palette {
+    pub background: Color,
+    pub border: Color,
+    pub shadow: Color,
+    pub label: Color,
+    pub treat: Color,
+    pub attractor: Color,
+    pub repeller: Color,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette {
+            background: Color(Rgba([0, 0, 0, 0])),
+            border: Color(Rgba([0, 0, 0, 255])),
+            shadow: Color(Rgba([0, 0, 0, 72])),
+            label: Color(Rgba([255, 255, 255, 255])),
+            treat: Color(Rgba([200, 116, 58, 255])),
+            attractor: Color(Rgba([86, 156, 214, 192])),
+            repeller: Color(Rgba([214, 86, 86, 192])),
+        }
+    }
+}
+
+/**
+ * A day of the year, written as `MM-DD` in JSON.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Day {
+    pub month: u32,
+    pub day: u32,
+}
+
+impl TryFrom<String> for Day {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let error = || format!("Day {} must look like MM-DD", value);
+        let (month, day) = value.split_once('-').ok_or_else(error)?;
+        let (month, day): (u32, u32) = (
+            month.parse().map_err(|_| error())?,
+            day.parse().map_err(|_| error())?,
+        );
+
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return Err(error());
+        }
+
+        Ok(Day { month, day })
+    }
+}
+
+impl Day {
+    /**
+     * Today, in UTC.
+     */
+    pub fn today() -> Self {
+        let days = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs() / 86_400)
+            .unwrap_or_default() as i64;
+
+        // civil from days, see https://howardhinnant.github.io/date_algorithms.html
+        let z = days + 719_468;
+        let day_of_era = z.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+
+        Day {
+            month: month as u32,
+            day: day as u32,
+        }
+    }
+}
+
+/**
+ * The days of the year a decoration is up, inclusive.
+ * Seasons may wrap around new year, e.g. `12-01` to `01-06`.
+ */
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Season {
+    pub from: Day,
+    pub to: Day,
+}
+
+impl Season {
+    pub fn contains(&self, day: &Day) -> bool {
+        if self.from <= self.to {
+            self.from <= *day && *day <= self.to
+        } else {
+            self.from <= *day || *day <= self.to
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DecorationLayer {
+    #[default]
+    Background,
+    Foreground,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DecorationMetadata {
+    sprite: String,
+    position: Vec2,
+    size: f64,
+    #[serde(default)]
+    layer: DecorationLayer,
+    season: Option<Season>,
+}
+
+/**
+ * A sprite placed at a fixed spot in the kennel.
+ * The sprite is centered on `position` and its longest side is `size`, both in kennel units.
+ */
+#[derive(Debug, Clone)]
+pub struct Decoration {
+    pub sprite: Sprite,
+    pub position: Vec2,
+    pub size: f64,
+    pub layer: DecorationLayer,
+    pub season: Option<Season>,
+}
+
+impl Decoration {
+    pub fn is_up(&self, day: &Day) -> bool {
+        self.season
+            .as_ref()
+            .is_none_or(|season| season.contains(day))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FloorMetadata {
+    texture: String,
+    tile_size: f64,
+}
+
+/**
+ * A texture tiled over the kennel, each tile `tile_size` kennel units wide.
+ */
+#[derive(Debug, Clone)]
+pub struct Floor {
+    pub texture: Sprite,
+    pub tile_size: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Border {
+    pub width: f64,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ThemeMetadata {
+    #[serde(default)]
+    palette: Palette,
+    background: Option<String>,
+    floor: Option<FloorMetadata>,
+    border: Option<Border>,
+    #[serde(default)]
+    shadows: bool,
+    #[serde(default)]
+    labels: bool,
+    #[serde(default)]
+    decorations: Vec<DecorationMetadata>,
+}
+
+/**
+ * How the kennel looks, loaded from `theme.json` in the data directory.
+ * Every file is relative to the data directory.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Theme {
+    pub palette: Palette,
+    pub background: Option<Sprite>,
+    pub floor: Option<Floor>,
+    pub border: Option<Border>,
+    pub shadows: bool,
+    pub labels: bool,
+    pub decorations: Vec<Decoration>,
+}
+
+impl Theme {
+    /**
+     * Loads the theme in the directory, falling back to the default theme if there isn't one.
+     */
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        let path = dir.join("theme.json");
+        if !path.exists() {
+            return Ok(Theme::default());
+        }
+
+        let json = std::fs::read_to_string(&path).map_err(|_| "Unable to read theme file")?;
+        let metadata: ThemeMetadata = serde_json::from_str(&json)
+            .map_err(|e| format!("Unable to deserialize theme. {}", e))?;
+
+        let load_sprite = |file: &String| {
+            Sprite::load(&dir.join(file)).map_err(|e| format!("Unable to load theme. {}", e))
+        };
+
+        let floor = match metadata.floor {
+            Some(floor) if floor.tile_size <= 0.0 => {
+                return Err("Floor `tile_size` must be positive".to_string());
+            }
+            Some(floor) => Some(Floor {
+                texture: load_sprite(&floor.texture)?,
+                tile_size: floor.tile_size,
+            }),
+            None => None,
+        };
+
+        let decorations = metadata
+            .decorations
+            .into_iter()
+            .map(|decoration| {
+                Ok(Decoration {
+                    sprite: load_sprite(&decoration.sprite)?,
+                    position: decoration.position,
+                    size: decoration.size,
+                    layer: decoration.layer,
+                    season: decoration.season,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Theme {
+            palette: metadata.palette,
+            background: metadata.background.as_ref().map(load_sprite).transpose()?,
+            floor,
+            border: metadata.border,
+            shadows: metadata.shadows,
+            labels: metadata.labels,
+            decorations,
+        })
+    }
+
+    /**
+     * The decorations of a layer that are up on the day.
+     */
+    pub fn decorations_on<'a>(
+        &'a self,
+        layer: &'a DecorationLayer,
+        day: &'a Day,
+    ) -> impl Iterator<Item = &'a Decoration> {
+        self.decorations
+            .iter()
+            .filter(move |decoration| decoration.layer == *layer && decoration.is_up(day))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color() {
+        let color = Color::try_from("#ff8000".to_string()).unwrap();
+        assert_eq!(color, Color(Rgba([255, 128, 0, 255])));
+        assert_eq!(color.to_hex(), "#ff8000ff");
+
+        let color = Color::try_from("#ff800080".to_string()).unwrap();
+        assert_eq!(color.0[3], 128);
+
+        assert!(Color::try_from("ff8000".to_string()).is_err());
+        assert!(Color::try_from("#ff80zz".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_season() {
+        let day = |value: &str| Day::try_from(value.to_string()).unwrap();
+        let october = Season {
+            from: day("10-01"),
+            to: day("10-31"),
+        };
+        assert!(october.contains(&day("10-31")));
+        assert!(!october.contains(&day("11-01")));
+
+        let holidays = Season {
+            from: day("12-01"),
+            to: day("01-06"),
+        };
+        assert!(holidays.contains(&day("12-25")));
+        assert!(holidays.contains(&day("01-01")));
+        assert!(!holidays.contains(&day("06-01")));
+
+        assert!(Day::try_from("13-01".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let metadata: ThemeMetadata = serde_json::from_str(
+            r##"{
+                "palette": { "background": "#f4e9d8" },
+                "border": { "width": 0.01 },
+                "shadows": true,
+                "decorations": [{
+                    "sprite": "pumpkin.png",
+                    "position": { "x": 0.1, "y": 0.9 },
+                    "size": 0.08,
+                    "layer": "foreground",
+                    "season": { "from": "10-01", "to": "10-31" }
+                }]
+            }"##,
+        )
+        .expect("Theme must deserialize");
+
+        assert_eq!(metadata.palette.background.0, Rgba([244, 233, 216, 255]));
+        assert_eq!(metadata.palette.border, Palette::default().border);
+        assert_eq!(metadata.decorations[0].layer, DecorationLayer::Foreground);
+        assert!(metadata.shadows);
+    }
+}
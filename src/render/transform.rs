@@ -0,0 +1,120 @@
+use crate::{creature::Creature, math::Vec2, sprite::Anchor};
+
+/**
+ * Maps kennel coordinates (the unit square) onto a canvas in pixels.
+ * The kennel is scaled to fit the shorter side of the canvas and centered along the longer one.
+ * Like the canvas, the kennel's y axis points down.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct Transform {
+    scale: f64,
+    offset: Vec2,
+}
+
+impl Transform {
+    pub fn new(canvas_width: u32, canvas_height: u32) -> Self {
+        let (width, height) = (canvas_width as f64, canvas_height as f64);
+        let scale = f64::min(width, height);
+        let offset = Vec2::new((width - scale) / 2.0, (height - scale) / 2.0);
+        Transform { scale, offset }
+    }
+
+    pub fn world_to_canvas(&self, position: &Vec2) -> Vec2 {
+        self.scale * position + self.offset
+    }
+
+    pub fn canvas_to_world(&self, position: &Vec2) -> Vec2 {
+        &(position - &self.offset) / self.scale
+    }
+
+    pub fn length_to_canvas(&self, length: f64) -> f64 {
+        self.scale * length
+    }
+
+    /**
+     * The point on the creature's collidable that its sprite's anchor sits on, in kennel coordinates.
+     */
+    pub fn anchor_point(creature: &Creature, anchor: &Anchor) -> Vec2 {
+        match anchor {
+            Anchor::Center => creature.position,
+            Anchor::Feet => creature.position + Vec2::new(0.0, creature.radius),
+        }
+    }
+
+    /**
+     * The canvas position of the top left corner of a creature's sprite, scaled to `width` by `height`.
+     * This may be off canvas, it's up to the caller to clip.
+     */
+    pub fn sprite_origin(&self, creature: &Creature, width: u32, height: u32) -> (i64, i64) {
+        let anchor = creature.sprite_sheet.anchor();
+        let anchor_point = self.world_to_canvas(&Transform::anchor_point(creature, anchor));
+        let (offset_x, offset_y) = anchor.sprite_offset(width, height);
+
+        (
+            (anchor_point.x - offset_x).round() as i64,
+            (anchor_point.y - offset_y).round() as i64,
+        )
+    }
+
+    /**
+     * How much to scale a creature's sprite so its longest side spans the collidable.
+     */
+    pub fn sprite_scale_factor(&self, creature: &Creature) -> f64 {
+        self.length_to_canvas(2.0 * creature.radius) * creature.sprite().scale_factor()
+    }
+
+    /**
+     * Where a creature's sprite is drawn on the canvas, as `(x, y, width, height)`.
+     * Like `sprite_origin`, this may hang off the canvas.
+     */
+    pub fn sprite_bounds(&self, creature: &Creature) -> (i64, i64, u32, u32) {
+        let (width, height) = creature
+            .sprite()
+            .scaled_size(self.sprite_scale_factor(creature));
+        let (x, y) = self.sprite_origin(creature, width, height);
+        (x, y, width, height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::creature::Metadata;
+
+    use super::*;
+
+    #[test]
+    fn test_world_to_canvas() {
+        let transform = Transform::new(200, 100);
+        let canvas_position = transform.world_to_canvas(&Vec2::new(0.5, 0.25));
+        assert_eq!(canvas_position.x, 100.0);
+        assert_eq!(canvas_position.y, 25.0);
+
+        let world_position = transform.canvas_to_world(&canvas_position);
+        assert_eq!(world_position.x, 0.5);
+        assert_eq!(world_position.y, 0.25);
+    }
+
+    #[test]
+    fn test_sprite_origin() {
+        let transform = Transform::new(100, 100);
+        let mut creature: Creature = Metadata::mock(0.1).into();
+        creature = creature.set_position(Vec2::new(0.5, 0.5));
+
+        // 20px is the diameter of the creature
+        assert_eq!(transform.sprite_origin(&creature, 20, 20), (40, 40));
+
+        // a sprite sticking out over the edge is placed off canvas rather than clamped
+        creature = creature.set_position(Vec2::new(0.05, 0.05));
+        assert_eq!(transform.sprite_origin(&creature, 20, 20), (-5, -5));
+    }
+
+    #[test]
+    fn test_anchor_point() {
+        let creature: Creature = Metadata::mock(0.1).into();
+        let creature = creature.set_position(Vec2::new(0.5, 0.5));
+        let feet = Transform::anchor_point(&creature, &Anchor::Feet);
+        assert_eq!(feet.x, 0.5);
+        assert_eq!(feet.y, 0.6);
+        assert_eq!(Anchor::Feet.sprite_offset(20, 10), (10.0, 10.0));
+    }
+}
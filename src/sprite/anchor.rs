@@ -0,0 +1,27 @@
+use serde::Deserialize;
+
+/**
+ * Which point of a sprite sits on its creature.
+ * `Center` puts the middle of the sprite on the middle of the collidable,
+ * `Feet` puts the bottom middle of the sprite on the bottom of the collidable.
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Anchor {
+    #[default]
+    Center,
+    Feet,
+}
+
+impl Anchor {
+    /**
+     * The anchor point within a sprite of the given size, from its top left corner.
+     */
+    pub fn sprite_offset(&self, width: u32, height: u32) -> (f64, f64) {
+        let (width, height) = (width as f64, height as f64);
+        match self {
+            Anchor::Center => (width / 2.0, height / 2.0),
+            Anchor::Feet => (width / 2.0, height),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use crate::sprite::base::Sprite;
+
+/**
+ * Frames per second used when metadata doesn't specify any timing.
+ * One frame a second matches the kennel's default tick.
+ */
+pub static DEFAULT_FPS: f64 = 1.0;
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub sprite: Sprite,
+    pub duration: Duration,
+}
+
+/**
+ * A looping sequence of frames, each shown for its own duration.
+ */
+#[derive(Debug, Default, Clone)]
+pub struct Animation {
+    frames: Vec<Frame>,
+}
+
+impl Animation {
+    pub fn new() -> Self {
+        Animation { frames: vec![] }
+    }
+
+    pub fn push(&mut self, sprite: Sprite, duration: Duration) {
+        self.frames.push(Frame { sprite, duration });
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /**
+     * The time it takes to play every frame once.
+     */
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().map(|frame| frame.duration).sum()
+    }
+
+    /**
+     * Gets the sprite of the nth frame, looping around.
+     */
+    pub fn get(&self, frame: usize) -> &Sprite {
+        &self.frames[frame % self.frames.len()].sprite
+    }
+
+    /**
+     * Gets the sprite showing after the animation has been playing for `elapsed`, looping around.
+     */
+    pub fn get_at(&self, elapsed: Duration) -> &Sprite {
+        let total = self.duration().as_nanos();
+        if total == 0 {
+            return self.get(0);
+        }
+
+        let mut remaining = elapsed.as_nanos() % total;
+        for frame in self.frames.iter() {
+            if remaining < frame.duration.as_nanos() {
+                return &frame.sprite;
+            }
+            remaining -= frame.duration.as_nanos();
+        }
+
+        self.get(0)
+    }
+
+    /**
+     * Creates a copy of the animation with every frame mirrored horizontally.
+     */
+    pub fn mirrored(&self) -> Self {
+        let frames = self
+            .frames
+            .iter()
+            .map(|frame| Frame {
+                sprite: frame.sprite.flipped(),
+                duration: frame.duration,
+            })
+            .collect();
+
+        Animation { frames }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{ImageFormat, RgbaImage};
+
+    use super::*;
+
+    fn sprite(width: u32) -> Sprite {
+        Sprite::from_image(RgbaImage::new(width, 1).into(), ImageFormat::Png)
+    }
+
+    #[test]
+    fn test_get_at() {
+        let mut animation = Animation::new();
+        animation.push(sprite(1), Duration::from_millis(100));
+        animation.push(sprite(2), Duration::from_millis(300));
+
+        let width_at = |millis| {
+            animation
+                .get_at(Duration::from_millis(millis))
+                .as_image()
+                .width()
+        };
+
+        assert_eq!(animation.duration(), Duration::from_millis(400));
+        assert_eq!(width_at(0), 1);
+        assert_eq!(width_at(99), 1);
+        assert_eq!(width_at(100), 2);
+        assert_eq!(width_at(399), 2);
+        assert_eq!(width_at(400), 1);
+        assert_eq!(width_at(1350), 2);
+    }
+}
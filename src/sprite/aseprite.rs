@@ -0,0 +1,165 @@
+use std::{path::Path, time::Duration};
+
+use serde::Deserialize;
+
+use crate::sprite::{Sprite, animation::Animation, atlas::Rect};
+
+/**
+ * The subset of Aseprite's JSON sprite sheet export that we care about.
+ * Both the "Hash" and "Array" frame layouts are supported.
+ * Each frame tag named after a sprite state (e.g. `idle`, `east`)
+ * becomes that state's animation, and each tag named after a transition
+ * (e.g. `sleep->idle`) becomes that transition's animation.
+ */
+#[derive(Debug, Deserialize)]
+struct Export {
+    frames: Frames,
+    meta: Meta,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Frames {
+    Array(Vec<Frame>),
+    Hash(serde_json::Map<String, serde_json::Value>),
+}
+
+#[derive(Debug, Deserialize)]
+struct Frame {
+    frame: Rect,
+    // milliseconds
+    duration: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Meta {
+    image: String,
+    #[serde(default, rename = "frameTags")]
+    frame_tags: Vec<Tag>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Tag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: Direction,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+enum Direction {
+    #[default]
+    Forward,
+    Reverse,
+    Pingpong,
+}
+
+impl Frames {
+    fn into_vec(self) -> Result<Vec<Frame>, String> {
+        match self {
+            Frames::Array(frames) => Ok(frames),
+            Frames::Hash(frames) => frames
+                .into_iter()
+                .map(|(name, value)| {
+                    serde_json::from_value(value)
+                        .map_err(|e| format!("Unable to read Aseprite frame {}. {}", name, e))
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Tag {
+    /**
+     * The frame indices of the tag in playback order.
+     */
+    fn indices(&self) -> Vec<usize> {
+        let forward: Vec<usize> = (self.from..=self.to).collect();
+        match self.direction {
+            Direction::Forward => forward,
+            Direction::Reverse => forward.into_iter().rev().collect(),
+            Direction::Pingpong => {
+                let back = forward.iter().rev().skip(1);
+                let back = back.take(forward.len().saturating_sub(2));
+                forward.iter().chain(back).copied().collect()
+            }
+        }
+    }
+}
+
+fn parse(json: &str) -> Result<Export, String> {
+    serde_json::from_str(json).map_err(|e| format!("Unable to deserialize Aseprite export. {}", e))
+}
+
+/**
+ * Loads the animation of every tag in an Aseprite JSON export, keeping each frame's duration.
+ * The atlas image is resolved relative to the JSON file.
+ */
+pub fn load(path: &Path) -> Result<Vec<(String, Animation)>, String> {
+    let json = std::fs::read_to_string(path)
+        .map_err(|_| format!("Unable to read Aseprite export {:?}", path))?;
+    let export = parse(&json)?;
+
+    let image_path = path
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(&export.meta.image);
+    let atlas = Sprite::load(&image_path)?;
+    let frames = export.frames.into_vec()?;
+
+    let mut animations = vec![];
+    for tag in export.meta.frame_tags.into_iter() {
+        let mut animation = Animation::new();
+        for idx in tag.indices().into_iter() {
+            let frame = frames.get(idx).ok_or(format!(
+                "Aseprite tag {} refers to missing frame {}",
+                tag.name, idx
+            ))?;
+            let sprite = atlas.slice(&frame.frame)?;
+            animation.push(sprite, Duration::from_millis(frame.duration));
+        }
+
+        animations.push((tag.name, animation));
+    }
+
+    Ok(animations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static HASH_EXPORT: &str = r#"{
+        "frames": {
+            "alt 0.aseprite": { "frame": { "x": 0, "y": 0, "w": 8, "h": 8 }, "duration": 100 },
+            "alt 1.aseprite": { "frame": { "x": 8, "y": 0, "w": 8, "h": 8 }, "duration": 100 },
+            "alt 2.aseprite": { "frame": { "x": 16, "y": 0, "w": 8, "h": 8 }, "duration": 100 }
+        },
+        "meta": {
+            "image": "alt.png",
+            "frameTags": [
+                { "name": "idle", "from": 0, "to": 2, "direction": "pingpong" },
+                { "name": "east", "from": 1, "to": 2, "direction": "reverse" },
+                { "name": "not a state", "from": 0, "to": 0 }
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn test_parse_hash() {
+        let export = parse(HASH_EXPORT).expect("Export must parse");
+        assert_eq!(export.meta.image, "alt.png");
+
+        let frames = export.frames.into_vec().expect("Frames must parse");
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[1].frame.x, 8);
+        assert_eq!(frames[1].duration, 100);
+
+        let tags = export.meta.frame_tags;
+        assert_eq!(tags[0].indices(), vec![0, 1, 2, 1]);
+        assert_eq!(tags[1].indices(), vec![2, 1]);
+        assert_eq!(tags[2].indices(), vec![0]);
+    }
+}
@@ -0,0 +1,45 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, LazyLock, Mutex, Weak},
+    time::SystemTime,
+};
+
+use crate::sprite::{atlas::Rect, base::Inner};
+
+/**
+ * Identifies where a shared sprite came from.
+ * Files are told apart by when they were last modified and how long they are too,
+ * so a file that changed on disk is loaded anew rather than shared with its old self.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    File(PathBuf, Option<SystemTime>, u64),
+    Slice(Box<Key>, Rect),
+    Mirror(Box<Key>),
+}
+
+/**
+ * Every sprite currently alive, keyed by where it came from.
+ * Entries are weak so sprites are freed once no sheet uses them anymore.
+ */
+static ASSETS: LazyLock<Mutex<HashMap<Key, Weak<Inner>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/**
+ * Gets the sprite for the key, creating (and caching) it if it isn't alive.
+ */
+pub fn get_or_insert<F>(key: Key, create: F) -> Result<Arc<Inner>, String>
+where
+    F: FnOnce() -> Result<Inner, String>,
+{
+    let mut assets = ASSETS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(inner) = assets.get(&key).and_then(Weak::upgrade) {
+        return Ok(inner);
+    }
+
+    let inner = Arc::new(create()?);
+    assets.retain(|_, inner| inner.strong_count() > 0);
+    assets.insert(key, Arc::downgrade(&inner));
+    Ok(inner)
+}
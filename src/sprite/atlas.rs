@@ -0,0 +1,77 @@
+use serde::Deserialize;
+
+/**
+ * A rectangle of pixels within an atlas image.
+ * Accepts both `width`/`height` and Aseprite's `w`/`h`.
+ */
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    #[serde(alias = "w")]
+    pub width: u32,
+    #[serde(alias = "h")]
+    pub height: u32,
+}
+
+/**
+ * A run of `frames` equally sized frames in a grid atlas,
+ * starting at `row`, `column` and reading left to right.
+ */
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct Row {
+    pub row: u32,
+    #[serde(default)]
+    pub column: u32,
+    pub frames: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Grid {
+    pub frame_width: u32,
+    pub frame_height: u32,
+}
+
+impl Grid {
+    pub fn rects(&self, row: &Row) -> impl Iterator<Item = Rect> {
+        let (frame_width, frame_height) = (self.frame_width, self.frame_height);
+        let y = row.row * frame_height;
+        (row.column..row.column + row.frames).map(move |column| Rect {
+            x: column * frame_width,
+            y,
+            width: frame_width,
+            height: frame_height,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_rects() {
+        let grid = Grid {
+            frame_width: 16,
+            frame_height: 8,
+        };
+        let row = Row {
+            row: 2,
+            column: 1,
+            frames: 3,
+        };
+
+        let rects: Vec<_> = grid.rects(&row).collect();
+        assert_eq!(rects.len(), 3);
+        assert_eq!(
+            rects[0],
+            Rect {
+                x: 16,
+                y: 16,
+                width: 16,
+                height: 8
+            }
+        );
+        assert_eq!(rects[2].x, 48);
+    }
+}
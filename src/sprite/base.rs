@@ -1,72 +1,333 @@
 use std::{
-    io::{BufWriter, Cursor},
+    io::Cursor,
     path::Path,
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
 };
 
-use image::{DynamicImage, ImageFormat, ImageReader, imageops::FilterType};
+use image::{DynamicImage, ImageFormat, ImageReader, RgbaImage, imageops::FilterType};
+
+use crate::sprite::{
+    assets::{self, Key},
+    atlas::Rect,
+    scaled,
+};
+
+static NEXT_SPRITE_ID: AtomicU64 = AtomicU64::new(0);
+
+fn next_sprite_id() -> u64 {
+    NEXT_SPRITE_ID.fetch_add(1, Ordering::Relaxed)
+}
 
 fn int_mult(float: f64, int: u32) -> u32 {
     (float * int as f64) as u32
 }
 
+/**
+ * Where the pixels of a sprite come from.
+ * Slices and mirrors are only cut out the first time the image is needed.
+ */
+#[derive(Debug)]
+enum Source {
+    File,
+    Slice(Sprite, Rect),
+    Mirror(Sprite),
+    Decoded,
+}
+
+#[derive(Debug)]
+pub(super) struct Inner {
+    id: u64,
+    source: Source,
+    width: u32,
+    height: u32,
+    format: ImageFormat,
+    key: Option<Key>,
+    image: OnceLock<DynamicImage>,
+    bytes: OnceLock<Vec<u8>>,
+}
+
+/**
+ * A cheaply clonable handle to a sprite image.
+ * Sprites loaded from the same file (or cut from the same atlas rectangle)
+ * share one handle through the asset cache.
+ */
 #[derive(Debug, Clone)]
 pub struct Sprite {
-    image: DynamicImage,
-    scale_factor: f64,
-    bytes: Vec<u8>,
-    format: ImageFormat,
+    inner: Arc<Inner>,
 }
 
 impl Sprite {
-    pub fn load(path: &Path) -> Self {
-        let image = ImageReader::open(path)
-            .unwrap_or_else(|_| panic!("Error opening sprite file: {:?}", path))
-            .decode()
-            .expect("Error decoding sprite");
+    /**
+     * Loads and decodes the sprite at the path, so broken files are caught up front.
+     * Its bytes are kept as read, so the encoded and the decoded sprite always agree.
+     */
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let metadata = std::fs::metadata(path)
+            .map_err(|_| format!("Error opening sprite file: {:?}", path))?;
+        let key = Key::File(path.to_path_buf(), metadata.modified().ok(), metadata.len());
+        let inner = assets::get_or_insert(key.clone(), || {
+            let bytes = std::fs::read(path)
+                .map_err(|_| format!("Error opening sprite file: {:?}", path))?;
+            let image = ImageReader::new(Cursor::new(&bytes))
+                .with_guessed_format()
+                .map_err(|_| format!("Error reading sprite file: {:?}", path))?
+                .decode()
+                .map_err(|e| format!("Error decoding sprite {:?}. {}", path, e))?;
 
-        let format = ImageFormat::from_path(path).unwrap_or(ImageFormat::Png);
+            let format = ImageFormat::from_path(path).unwrap_or(ImageFormat::Png);
 
-        let mut cached_buffer = BufWriter::new(Cursor::new(Vec::new()));
-        image
-            .write_to(&mut cached_buffer, format)
-            .expect("Error caching sprite");
+            Ok(Inner {
+                id: next_sprite_id(),
+                source: Source::File,
+                width: image.width(),
+                height: image.height(),
+                format,
+                key: Some(key),
+                image: OnceLock::from(image),
+                bytes: OnceLock::from(bytes),
+            })
+        })?;
 
-        let bytes = cached_buffer
-            .into_inner()
-            .expect("Error writing sprite buffer cache")
-            .into_inner();
+        Ok(Sprite { inner })
+    }
 
-        let scale_factor = 1.0 / (u32::max(image.width(), image.height()) as f64);
+    /**
+     * Creates a sprite out of an already decoded image.
+     * These are not shared through the asset cache.
+     */
+    pub fn from_image(image: DynamicImage, format: ImageFormat) -> Self {
+        let inner = Inner {
+            id: next_sprite_id(),
+            source: Source::Decoded,
+            width: image.width(),
+            height: image.height(),
+            format,
+            key: None,
+            image: OnceLock::from(image),
+            bytes: OnceLock::new(),
+        };
 
         Sprite {
-            image,
-            bytes,
-            scale_factor,
-            format,
+            inner: Arc::new(inner),
         }
     }
 
+    /**
+     * Derives a new sprite from this one, sharing it through the asset cache
+     * if this sprite is shared too.
+     */
+    fn derive(&self, key: Option<Key>, source: Source, width: u32, height: u32) -> Self {
+        let create = |key| {
+            Ok(Inner {
+                id: next_sprite_id(),
+                source,
+                width,
+                height,
+                format: self.inner.format,
+                key,
+                image: OnceLock::new(),
+                bytes: OnceLock::new(),
+            })
+        };
+
+        let inner = match key {
+            Some(key) => assets::get_or_insert(key.clone(), || create(Some(key))),
+            None => create(None).map(Arc::new),
+        };
+
+        Sprite {
+            inner: inner.expect("Deriving a sprite cannot fail"),
+        }
+    }
+
+    /**
+     * Cuts a rectangle out of the sprite, e.g. a frame out of an atlas.
+     */
+    pub fn slice(&self, rect: &Rect) -> Result<Self, String> {
+        let (width, height) = (self.width(), self.height());
+        let is_in_bounds = rect.width > 0
+            && rect.height > 0
+            && u64::from(rect.x) + u64::from(rect.width) <= u64::from(width)
+            && u64::from(rect.y) + u64::from(rect.height) <= u64::from(height);
+
+        if !is_in_bounds {
+            return Err(format!(
+                "Frame {:?} does not fit in the {}x{} atlas",
+                rect, width, height
+            ));
+        }
+
+        let key = self
+            .inner
+            .key
+            .as_ref()
+            .map(|key| Key::Slice(Box::new(key.clone()), *rect));
+        let source = Source::Slice(self.clone(), *rect);
+        Ok(self.derive(key, source, rect.width, rect.height))
+    }
+
+    /**
+     * Creates a horizontally mirrored copy of the sprite.
+     */
+    pub fn flipped(&self) -> Self {
+        let key = self
+            .inner
+            .key
+            .as_ref()
+            .map(|key| Key::Mirror(Box::new(key.clone())));
+        let source = Source::Mirror(self.clone());
+        self.derive(key, source, self.width(), self.height())
+    }
+
     pub fn get_scaled_image(&self, scale_factor: f64) -> DynamicImage {
-        let (width, height) = (
-            int_mult(scale_factor, self.image.width()),
-            int_mult(scale_factor, self.image.height()),
-        );
-        self.image.resize(width, height, FilterType::Nearest)
+        let (width, height) = self.scaled_size(scale_factor);
+        self.as_image().resize(width, height, FilterType::Nearest)
+    }
+
+    /**
+     * Same as `get_scaled_image`, but shared through a bounded cache of scaled sprites
+     * so rendering the same sprite at the same size again is free.
+     */
+    pub fn get_cached_scaled_image(&self, scale_factor: f64) -> Arc<RgbaImage> {
+        let (width, height) = self.scaled_size(scale_factor);
+        self.get_cached_resized_image(width, height)
     }
 
-    pub fn bytes(&self) -> Vec<u8> {
-        self.bytes.to_vec()
+    /**
+     * The sprite stretched to exactly `width` by `height`, through the same cache.
+     */
+    pub fn get_cached_resized_image(&self, width: u32, height: u32) -> Arc<RgbaImage> {
+        scaled::get_or_insert((self.inner.id, width, height), || {
+            self.as_image()
+                .resize_exact(width, height, FilterType::Nearest)
+                .into_rgba8()
+        })
+    }
+
+    /**
+     * The sprite encoded in its format.
+     * Sprites straight from a file are the file's bytes as they were loaded.
+     */
+    pub fn bytes(&self) -> Result<Vec<u8>, String> {
+        if let Some(bytes) = self.inner.bytes.get() {
+            return Ok(bytes.clone());
+        }
+
+        let mut buffer = Cursor::new(Vec::new());
+        self.as_image()
+            .write_to(&mut buffer, self.inner.format)
+            .map_err(|e| format!("Error encoding sprite. {}", e))?;
+        Ok(self.inner.bytes.get_or_init(|| buffer.into_inner()).clone())
     }
 
     pub fn as_image(&self) -> &DynamicImage {
-        &self.image
+        self.inner.image.get_or_init(|| match &self.inner.source {
+            Source::Slice(sprite, rect) => {
+                sprite
+                    .as_image()
+                    .crop_imm(rect.x, rect.y, rect.width, rect.height)
+            }
+            Source::Mirror(sprite) => sprite.as_image().fliph(),
+            Source::File | Source::Decoded => {
+                unreachable!("Loaded and decoded sprites are created with their image")
+            }
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.inner.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.inner.height
+    }
+
+    /**
+     * The size of the sprite scaled by the factor, without scaling it.
+     */
+    pub fn scaled_size(&self, scale_factor: f64) -> (u32, u32) {
+        (
+            int_mult(scale_factor, self.width()),
+            int_mult(scale_factor, self.height()),
+        )
     }
 
     pub(crate) fn scale_factor(&self) -> f64 {
-        self.scale_factor
+        1.0 / (u32::max(self.width(), self.height()) as f64)
     }
 
     pub fn format(&self) -> ImageFormat {
-        self.format
+        self.inner.format
+    }
+
+    /**
+     * Whether both handles point to the same image.
+     */
+    pub fn ptr_eq(&self, other: &Sprite) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_slice() {
+        let mut image = RgbaImage::new(4, 2);
+        image.put_pixel(2, 1, Rgba([255, 0, 0, 255]));
+        let atlas = Sprite::from_image(image.into(), ImageFormat::Png);
+
+        let rect = Rect {
+            x: 2,
+            y: 0,
+            width: 2,
+            height: 2,
+        };
+        let sprite = atlas.slice(&rect).expect("Frame must fit in atlas");
+        let pixel = sprite.as_image().to_rgba8().get_pixel(0, 1).0;
+        assert_eq!(pixel, [255, 0, 0, 255]);
+
+        let out_of_bounds = Rect { x: 3, ..rect };
+        assert!(atlas.slice(&out_of_bounds).is_err());
+    }
+
+    #[test]
+    fn test_shared_loading() {
+        let path = PathBuf::from("./data/alt/idle_01.png");
+        let sprite = Sprite::load(&path).expect("Sample sprite must load");
+        let same_sprite = Sprite::load(&path).expect("Sample sprite must load");
+        assert!(sprite.ptr_eq(&same_sprite));
+        assert!(sprite.flipped().ptr_eq(&same_sprite.flipped()));
+
+        let other_sprite = Sprite::load(&PathBuf::from("./data/alt/idle_02.png")).unwrap();
+        assert!(!sprite.ptr_eq(&other_sprite));
+
+        assert_eq!(sprite.as_image().width(), sprite.width());
+        assert_eq!(sprite.bytes(), Ok(std::fs::read(&path).unwrap()));
+    }
+
+    #[test]
+    fn test_load_corrupt() {
+        let dir = std::env::temp_dir().join(format!("kennel-corrupt-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let bytes = std::fs::read("./data/alt/idle_01.png").unwrap();
+
+        // the header alone is fine, but the pixels are cut off
+        let truncated = dir.join("truncated.png");
+        std::fs::write(&truncated, &bytes[..bytes.len() / 2]).unwrap();
+        assert!(Sprite::load(&truncated).is_err());
+
+        let missing = dir.join("missing.png");
+        assert!(Sprite::load(&missing).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
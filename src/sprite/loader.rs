@@ -1,83 +1,353 @@
-use std::path::Path;
+use std::{collections::HashMap, path::Path, time::Duration};
 
 use serde::Deserialize;
 
-use crate::sprite::Sheet;
+use crate::sprite::{
+    Anchor, Sheet, Sprite, State,
+    animation::{Animation, DEFAULT_FPS},
+    aseprite,
+    atlas::{Grid, Rect, Row},
+    state::parse_transition,
+};
 
+/**
+ * A single frame, either its own image file
+ * or a rectangle within the atlas.
+ */
 #[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Frame {
+    File(String),
+    Rect(Rect),
+}
+
+/**
+ * The frames of a sprite state, either listed one by one
+ * or as a row of the atlas grid.
+ */
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Frames {
+    Row(Row),
+    List(Vec<Frame>),
+}
+
+impl Frames {
+    fn load(
+        &self,
+        path_prefix: &Path,
+        atlas: Option<&Sprite>,
+        grid: Option<&Grid>,
+    ) -> Result<Vec<Sprite>, String> {
+        let atlas_or_err = || atlas.ok_or("Atlas frames were given without an `atlas` image");
+        match self {
+            Frames::Row(row) => {
+                let grid = grid.ok_or("Grid rows require `frame_width` and `frame_height`")?;
+                let atlas = atlas_or_err()?;
+                grid.rects(row).map(|rect| atlas.slice(&rect)).collect()
+            }
+            Frames::List(frames) => frames
+                .iter()
+                .map(|frame| match frame {
+                    Frame::File(path) => Sprite::load(&path_prefix.join(path)),
+                    Frame::Rect(rect) => atlas_or_err()?.slice(rect),
+                })
+                .collect(),
+        }
+    }
+}
+
+/**
+ * The frames of a sprite state along with optional timing,
+ * either a frame rate or a duration in milliseconds for every frame.
+ */
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Timed {
+    frames: Frames,
+    fps: Option<f64>,
+    durations: Option<Vec<u64>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Entry {
+    Timed(Timed),
+    Plain(Frames),
+}
+
+fn frame_duration(fps: f64) -> Result<Duration, String> {
+    if !fps.is_finite() || fps <= 0.0 {
+        return Err(format!("`fps` must be positive, got {}", fps));
+    }
+
+    Duration::try_from_secs_f64(1.0 / fps)
+        .map_err(|e| format!("`fps` of {} is too slow to play. {}", fps, e))
+}
+
+impl Entry {
+    fn load(
+        &self,
+        path_prefix: &Path,
+        atlas: Option<&Sprite>,
+        grid: Option<&Grid>,
+        default_fps: f64,
+    ) -> Result<Animation, String> {
+        let (frames, fps, durations) = match self {
+            Entry::Timed(timed) => (&timed.frames, timed.fps, timed.durations.as_ref()),
+            Entry::Plain(frames) => (frames, None, None),
+        };
+
+        let sprites = frames.load(path_prefix, atlas, grid)?;
+        let durations: Vec<Duration> = match (fps, durations) {
+            (Some(_), Some(_)) => return Err("Give either `fps` or `durations`, not both".into()),
+            (_, Some(durations)) if durations.len() != sprites.len() => {
+                return Err(format!(
+                    "Got {} durations for {} frames",
+                    durations.len(),
+                    sprites.len()
+                ));
+            }
+            (_, Some(durations)) => durations
+                .iter()
+                .copied()
+                .map(Duration::from_millis)
+                .collect(),
+            (fps, None) => vec![frame_duration(fps.unwrap_or(default_fps))?; sprites.len()],
+        };
+
+        let mut animation = Animation::new();
+        for (sprite, duration) in sprites.into_iter().zip(durations) {
+            animation.push(sprite, duration);
+        }
+
+        Ok(animation)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Loader {
-    idle: Vec<String>,
-    sleep: Vec<String>,
-    east: Vec<String>,
-    northeast: Vec<String>,
-    north: Vec<String>,
-    northwest: Vec<String>,
-    west: Vec<String>,
-    southwest: Vec<String>,
-    south: Vec<String>,
-    southeast: Vec<String>,
+    atlas: Option<String>,
+    frame_width: Option<u32>,
+    frame_height: Option<u32>,
+    aseprite: Option<String>,
+    fps: Option<f64>,
+    #[serde(default)]
+    anchor: Anchor,
+    idle: Option<Entry>,
+    sleep: Option<Entry>,
+    east: Option<Entry>,
+    northeast: Option<Entry>,
+    north: Option<Entry>,
+    northwest: Option<Entry>,
+    west: Option<Entry>,
+    southwest: Option<Entry>,
+    south: Option<Entry>,
+    southeast: Option<Entry>,
+    sniff: Option<Entry>,
+    nap: Option<Entry>,
+    wave: Option<Entry>,
+    #[serde(default)]
+    transitions: HashMap<String, Entry>,
 }
 
 impl Loader {
     #[cfg(test)]
     pub fn new() -> Self {
         Loader {
-            idle: vec![],
-            sleep: vec![],
-            east: vec![],
-            northeast: vec![],
-            north: vec![],
-            northwest: vec![],
-            west: vec![],
-            southwest: vec![],
-            south: vec![],
-            southeast: vec![],
+            atlas: None,
+            frame_width: None,
+            frame_height: None,
+            aseprite: None,
+            fps: None,
+            anchor: Anchor::Center,
+            idle: None,
+            sleep: None,
+            east: None,
+            northeast: None,
+            north: None,
+            northwest: None,
+            west: None,
+            southwest: None,
+            south: None,
+            southeast: None,
+            sniff: None,
+            nap: None,
+            wave: None,
+            transitions: HashMap::new(),
         }
     }
 
-    pub fn load(self, path_prefix: &Path) -> Sheet {
-        let mut sheet = Sheet::new();
-
-        for path in self.idle.into_iter() {
-            sheet.push_idle(path_prefix.join(path));
+    fn entry(&self, sprite_state: &State) -> Option<&Entry> {
+        match sprite_state {
+            State::Idle => self.idle.as_ref(),
+            State::Sleep => self.sleep.as_ref(),
+            State::East => self.east.as_ref(),
+            State::Northeast => self.northeast.as_ref(),
+            State::North => self.north.as_ref(),
+            State::Northwest => self.northwest.as_ref(),
+            State::West => self.west.as_ref(),
+            State::Southwest => self.southwest.as_ref(),
+            State::South => self.south.as_ref(),
+            State::Southeast => self.southeast.as_ref(),
+            State::Sniff => self.sniff.as_ref(),
+            State::Nap => self.nap.as_ref(),
+            State::Wave => self.wave.as_ref(),
         }
+    }
 
-        for path in self.sleep.into_iter() {
-            sheet.push_sleep(path_prefix.join(path));
+    fn grid(&self) -> Result<Option<Grid>, String> {
+        match (self.frame_width, self.frame_height) {
+            (Some(frame_width), Some(frame_height)) => Ok(Some(Grid {
+                frame_width,
+                frame_height,
+            })),
+            (None, None) => Ok(None),
+            _ => Err("`frame_width` and `frame_height` must be given together".to_string()),
         }
+    }
 
-        for path in self.east.into_iter() {
-            sheet.push_east(path_prefix.join(path));
-        }
+    /**
+     * Loads every sprite into a sheet.
+     * Frames without explicit timing play at `fps`, or `DEFAULT_FPS` if that's missing too.
+     * Frames listed explicitly for a state or transition take precedence
+     * over the frames of an Aseprite tag with the same name.
+     * Only `idle` is required, the other states are derived from it
+     * (see `State::fallbacks`).
+     */
+    pub fn load(self, path_prefix: &Path) -> Result<Sheet, String> {
+        let grid = self.grid()?;
+        let atlas = self
+            .atlas
+            .as_ref()
+            .map(|atlas| Sprite::load(&path_prefix.join(atlas)))
+            .transpose()?;
 
-        for path in self.northeast.into_iter() {
-            sheet.push_northeast(path_prefix.join(path));
-        }
+        let mut sheet = Sheet::new();
+        sheet.set_anchor(self.anchor);
 
-        for path in self.north.into_iter() {
-            sheet.push_north(path_prefix.join(path));
+        if let Some(aseprite) = &self.aseprite {
+            for (name, animation) in aseprite::load(&path_prefix.join(aseprite))? {
+                if let Ok(sprite_state) = State::try_from(name.as_str()) {
+                    sheet.set(&sprite_state, animation);
+                } else if let Ok((from, to)) = parse_transition(&name) {
+                    sheet.set_transition(&from, &to, animation);
+                }
+            }
         }
 
-        for path in self.northwest.into_iter() {
-            sheet.push_northwest(path_prefix.join(path));
-        }
+        let default_fps = self.fps.unwrap_or(DEFAULT_FPS);
+        for sprite_state in State::ALL.iter() {
+            let Some(entry) = self.entry(sprite_state) else {
+                continue;
+            };
 
-        for path in self.west.into_iter() {
-            sheet.push_west(path_prefix.join(path));
+            let animation = entry
+                .load(path_prefix, atlas.as_ref(), grid.as_ref(), default_fps)
+                .map_err(|e| format!("Unable to load {} sprites. {}", sprite_state, e))?;
+            sheet.set(sprite_state, animation);
         }
 
-        for path in self.southwest.into_iter() {
-            sheet.push_southwest(path_prefix.join(path));
+        for (name, entry) in self.transitions.iter() {
+            let (from, to) = parse_transition(name)
+                .map_err(|_| format!("Transition {} must look like `sleep->idle`", name))?;
+            let animation = entry
+                .load(path_prefix, atlas.as_ref(), grid.as_ref(), default_fps)
+                .map_err(|e| format!("Unable to load {} sprites. {}", name, e))?;
+            sheet.set_transition(&from, &to, animation);
         }
 
-        for path in self.south.into_iter() {
-            sheet.push_south(path_prefix.join(path));
-        }
+        sheet.derive_missing()?;
 
-        for path in self.southeast.into_iter() {
-            sheet.push_southeast(path_prefix.join(path));
-        }
+        Ok(sheet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_frames() {
+        let loader: Loader = serde_json::from_str(
+            r#"{
+                "atlas": "alt.png",
+                "frame_width": 32,
+                "frame_height": 32,
+                "idle": { "row": 0, "frames": 5 },
+                "sleep": [{ "x": 0, "y": 32, "width": 32, "height": 32 }],
+                "east": ["running_right_01.png"]
+            }"#,
+        )
+        .expect("Loader must deserialize");
+
+        assert!(matches!(loader.idle, Some(Entry::Plain(Frames::Row(_)))));
+        assert!(matches!(
+            loader.sleep.as_ref(),
+            Some(Entry::Plain(Frames::List(frames))) if matches!(frames[0], Frame::Rect(_))
+        ));
+        assert!(matches!(
+            loader.east.as_ref(),
+            Some(Entry::Plain(Frames::List(frames))) if matches!(frames[0], Frame::File(_))
+        ));
+        assert!(loader.north.is_none());
+    }
+
+    #[test]
+    fn test_deserialize_timing() {
+        let loader: Loader = serde_json::from_str(
+            r#"{
+                "fps": 12,
+                "idle": { "frames": { "row": 0, "frames": 5 }, "fps": 8 },
+                "sleep": { "frames": ["sleep_01.png", "sleep_02.png"], "durations": [500, 1500] }
+            }"#,
+        )
+        .expect("Loader must deserialize");
+
+        assert_eq!(loader.fps, Some(12.0));
+        assert!(matches!(
+            loader.idle.as_ref(),
+            Some(Entry::Timed(Timed {
+                frames: Frames::Row(_),
+                fps: Some(8.0),
+                durations: None
+            }))
+        ));
+        assert!(matches!(
+            loader.sleep.as_ref(),
+            Some(Entry::Timed(Timed { fps: None, durations: Some(durations), .. })) if durations.len() == 2
+        ));
+        assert!(frame_duration(0.0).is_err());
+        assert!(frame_duration(1e-300).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_transitions() {
+        let loader: Loader = serde_json::from_str(
+            r#"{
+                "idle": ["idle_01.png"],
+                "transitions": {
+                    "sleep->idle": ["waking_01.png", "waking_02.png"],
+                    "idle->sleep": { "frames": ["lying_01.png"], "fps": 4 }
+                }
+            }"#,
+        )
+        .expect("Loader must deserialize");
+
+        assert_eq!(loader.transitions.len(), 2);
+        assert_eq!(
+            parse_transition("sleep->idle"),
+            Ok((State::Sleep, State::Idle))
+        );
+        assert!(parse_transition("sleep").is_err());
+        assert!(parse_transition("sleep->dance").is_err());
+    }
 
-        sheet
+    #[test]
+    fn test_grid_requires_both_dimensions() {
+        let loader: Loader =
+            serde_json::from_str(r#"{ "frame_width": 32 }"#).expect("Loader must deserialize");
+        assert!(loader.grid().is_err());
     }
 }
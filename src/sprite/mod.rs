@@ -1,9 +1,17 @@
+pub use anchor::Anchor;
+pub use animation::Animation;
 pub use base::Sprite;
 pub use loader::Loader;
 pub use sheet::Sheet;
 pub use state::State;
 
+mod anchor;
+mod animation;
+mod aseprite;
+mod assets;
+mod atlas;
 mod base;
 mod loader;
+mod scaled;
 mod sheet;
 mod state;
@@ -0,0 +1,131 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex},
+};
+
+use image::RgbaImage;
+
+/**
+ * How many bytes of scaled sprites to keep around before evicting the least recently used.
+ */
+pub static SCALED_CACHE_CAPACITY: usize = 64 * 1024 * 1024;
+
+type Key = (u64, u32, u32);
+
+struct Entry {
+    image: Arc<RgbaImage>,
+    last_used: u64,
+}
+
+/**
+ * Scaled copies of sprites keyed by (sprite id, width, height)
+ * so repeated renders at the same size don't resize every time.
+ */
+struct ScaledCache {
+    entries: HashMap<Key, Entry>,
+    size: usize,
+    capacity: usize,
+    clock: u64,
+}
+
+static SCALED: LazyLock<Mutex<ScaledCache>> =
+    LazyLock::new(|| Mutex::new(ScaledCache::new(SCALED_CACHE_CAPACITY)));
+
+fn image_size(image: &RgbaImage) -> usize {
+    image.as_raw().len()
+}
+
+impl ScaledCache {
+    fn new(capacity: usize) -> Self {
+        ScaledCache {
+            entries: HashMap::new(),
+            size: 0,
+            capacity,
+            clock: 0,
+        }
+    }
+
+    fn get(&mut self, key: &Key) -> Option<Arc<RgbaImage>> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        entry.last_used = self.clock;
+        Some(entry.image.clone())
+    }
+
+    fn insert(&mut self, key: Key, image: Arc<RgbaImage>) {
+        let size = image_size(&image);
+        if size > self.capacity {
+            return;
+        }
+
+        while self.size + size > self.capacity {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| *key)
+            else {
+                break;
+            };
+
+            if let Some(entry) = self.entries.remove(&oldest) {
+                self.size -= image_size(&entry.image);
+            }
+        }
+
+        self.clock += 1;
+        let entry = Entry {
+            image,
+            last_used: self.clock,
+        };
+        if let Some(replaced) = self.entries.insert(key, entry) {
+            self.size -= image_size(&replaced.image);
+        }
+        self.size += size;
+    }
+}
+
+/**
+ * Gets the scaled image from the cache, or scales and caches it.
+ * The lock isn't held while scaling, so two renders may scale the same sprite at once.
+ */
+pub fn get_or_insert<F>(key: Key, scale: F) -> Arc<RgbaImage>
+where
+    F: FnOnce() -> RgbaImage,
+{
+    let lock = || SCALED.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(image) = lock().get(&key) {
+        return image;
+    }
+
+    let image = Arc::new(scale());
+    lock().insert(key, image.clone());
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eviction() {
+        let image = |width| Arc::new(RgbaImage::new(width, 1));
+        // each pixel is 4 bytes
+        let mut cache = ScaledCache::new(4 * 10);
+
+        cache.insert((0, 4, 1), image(4));
+        cache.insert((1, 4, 1), image(4));
+        assert!(cache.get(&(0, 4, 1)).is_some());
+
+        // evicts 1, the least recently used
+        cache.insert((2, 4, 1), image(4));
+        assert!(cache.get(&(1, 4, 1)).is_none());
+        assert!(cache.get(&(0, 4, 1)).is_some());
+        assert!(cache.get(&(2, 4, 1)).is_some());
+        assert_eq!(cache.size, 4 * 8);
+
+        // too big to ever fit
+        cache.insert((3, 11, 1), image(11));
+        assert!(cache.get(&(3, 11, 1)).is_none());
+    }
+}
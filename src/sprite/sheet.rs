@@ -1,104 +1,242 @@
-use std::path::PathBuf;
+use std::{collections::HashMap, time::Duration};
 
-use crate::sprite::base::Sprite;
+use crate::sprite::{anchor::Anchor, animation::Animation, base::Sprite};
 
 use super::state::State;
 
 #[derive(Debug, Default, Clone)]
 pub struct Sheet {
-    idle: Vec<Sprite>,
-    sleep: Vec<Sprite>,
-    east: Vec<Sprite>,
-    northeast: Vec<Sprite>,
-    north: Vec<Sprite>,
-    northwest: Vec<Sprite>,
-    west: Vec<Sprite>,
-    southwest: Vec<Sprite>,
-    south: Vec<Sprite>,
-    southeast: Vec<Sprite>,
+    idle: Animation,
+    sleep: Animation,
+    east: Animation,
+    northeast: Animation,
+    north: Animation,
+    northwest: Animation,
+    west: Animation,
+    southwest: Animation,
+    south: Animation,
+    southeast: Animation,
+    sniff: Animation,
+    nap: Animation,
+    wave: Animation,
+    transitions: HashMap<(State, State), Animation>,
+    anchor: Anchor,
 }
 
 impl Sheet {
     pub(crate) fn new() -> Self {
         Sheet {
-            idle: vec![],
-            sleep: vec![],
-            east: vec![],
-            northeast: vec![],
-            north: vec![],
-            northwest: vec![],
-            west: vec![],
-            southwest: vec![],
-            south: vec![],
-            southeast: vec![],
+            idle: Animation::new(),
+            sleep: Animation::new(),
+            east: Animation::new(),
+            northeast: Animation::new(),
+            north: Animation::new(),
+            northwest: Animation::new(),
+            west: Animation::new(),
+            southwest: Animation::new(),
+            south: Animation::new(),
+            southeast: Animation::new(),
+            sniff: Animation::new(),
+            nap: Animation::new(),
+            wave: Animation::new(),
+            transitions: HashMap::new(),
+            anchor: Anchor::default(),
         }
     }
 
-    pub(crate) fn push_idle(&mut self, path: PathBuf) {
-        self.idle.push(Sprite::load(&path));
+    fn animation_mut(&mut self, sprite_state: &State) -> &mut Animation {
+        match sprite_state {
+            State::Idle => &mut self.idle,
+            State::Sleep => &mut self.sleep,
+            State::East => &mut self.east,
+            State::Northeast => &mut self.northeast,
+            State::North => &mut self.north,
+            State::Northwest => &mut self.northwest,
+            State::West => &mut self.west,
+            State::Southwest => &mut self.southwest,
+            State::South => &mut self.south,
+            State::Southeast => &mut self.southeast,
+            State::Sniff => &mut self.sniff,
+            State::Nap => &mut self.nap,
+            State::Wave => &mut self.wave,
+        }
     }
 
-    pub(crate) fn push_sleep(&mut self, path: PathBuf) {
-        self.sleep.push(Sprite::load(&path));
+    pub(crate) fn set(&mut self, sprite_state: &State, animation: Animation) {
+        *self.animation_mut(sprite_state) = animation;
     }
 
-    pub(crate) fn push_east(&mut self, path: PathBuf) {
-        self.east.push(Sprite::load(&path));
+    pub(crate) fn set_anchor(&mut self, anchor: Anchor) {
+        self.anchor = anchor;
     }
 
-    pub(crate) fn push_northeast(&mut self, path: PathBuf) {
-        self.northeast.push(Sprite::load(&path));
+    pub fn anchor(&self) -> &Anchor {
+        &self.anchor
     }
 
-    pub(crate) fn push_north(&mut self, path: PathBuf) {
-        self.north.push(Sprite::load(&path));
+    pub(crate) fn set_transition(&mut self, from: &State, to: &State, animation: Animation) {
+        self.transitions.insert((*from, *to), animation);
     }
 
-    pub(crate) fn push_northwest(&mut self, path: PathBuf) {
-        self.northwest.push(Sprite::load(&path));
+    /**
+     * Gets the animation played once when switching from one state to another, if there is one.
+     */
+    pub fn transition(&self, from: &State, to: &State) -> Option<&Animation> {
+        self.transitions.get(&(*from, *to))
     }
 
-    pub(crate) fn push_west(&mut self, path: PathBuf) {
-        self.west.push(Sprite::load(&path));
+    pub fn animation(&self, sprite_state: &State) -> &Animation {
+        match sprite_state {
+            State::Idle => &self.idle,
+            State::Sleep => &self.sleep,
+            State::East => &self.east,
+            State::Northeast => &self.northeast,
+            State::North => &self.north,
+            State::Northwest => &self.northwest,
+            State::West => &self.west,
+            State::Southwest => &self.southwest,
+            State::South => &self.south,
+            State::Southeast => &self.southeast,
+            State::Sniff => &self.sniff,
+            State::Nap => &self.nap,
+            State::Wave => &self.wave,
+        }
+    }
+
+    /**
+     * Fills in every state without frames by borrowing (and possibly mirroring)
+     * the frames of the first state in its fallback list that was actually provided.
+     */
+    pub(crate) fn derive_missing(&mut self) -> Result<(), String> {
+        let provided: Vec<State> = State::ALL
+            .into_iter()
+            .filter(|sprite_state| !self.animation(sprite_state).is_empty())
+            .collect();
+
+        for sprite_state in State::ALL.iter() {
+            if provided.contains(sprite_state) {
+                continue;
+            }
+
+            let Some((source, is_mirrored)) = sprite_state
+                .fallbacks()
+                .iter()
+                .find(|(source, _)| provided.contains(source))
+            else {
+                return Err(format!("No sprites were given for state {}", sprite_state));
+            };
+
+            let animation = if *is_mirrored {
+                self.animation(source).mirrored()
+            } else {
+                self.animation(source).clone()
+            };
+
+            self.set(sprite_state, animation);
+        }
+
+        Ok(())
     }
 
-    pub(crate) fn push_southwest(&mut self, path: PathBuf) {
-        self.southwest.push(Sprite::load(&path));
+    /**
+     * Gets the nth frame of the state, regardless of frame timing.
+     */
+    pub fn get_sprite(&self, sprite_state: &State, frame: usize) -> &Sprite {
+        self.animation(sprite_state).get(frame)
     }
 
-    pub(crate) fn push_south(&mut self, path: PathBuf) {
-        self.south.push(Sprite::load(&path));
+    /**
+     * Gets the frame showing after being in the state for `elapsed`.
+     */
+    pub fn get_sprite_at(&self, sprite_state: &State, elapsed: Duration) -> &Sprite {
+        self.animation(sprite_state).get_at(elapsed)
     }
 
-    pub(crate) fn push_southeast(&mut self, path: PathBuf) {
-        self.southeast.push(Sprite::load(&path));
+    /**
+     * Gets the frame showing `elapsed` after switching from `from` to `sprite_state`.
+     * The transition between the two plays once before the state's own loop starts.
+     */
+    pub fn get_transition_sprite_at(
+        &self,
+        from: &State,
+        sprite_state: &State,
+        elapsed: Duration,
+    ) -> &Sprite {
+        match self.transition(from, sprite_state) {
+            Some(transition) if elapsed < transition.duration() => transition.get_at(elapsed),
+            Some(transition) => self.get_sprite_at(sprite_state, elapsed - transition.duration()),
+            None => self.get_sprite_at(sprite_state, elapsed),
+        }
     }
+}
 
-    pub fn get_sprite(&self, sprite_state: &State, frame: usize) -> &Sprite {
-        let frame_idx = match sprite_state {
-            State::Idle => frame % self.idle.len(),
-            State::Sleep => frame % self.sleep.len(),
-            State::East => frame % self.east.len(),
-            State::Northeast => frame % self.northeast.len(),
-            State::North => frame % self.north.len(),
-            State::Northwest => frame % self.northwest.len(),
-            State::West => frame % self.west.len(),
-            State::Southwest => frame % self.southwest.len(),
-            State::South => frame % self.south.len(),
-            State::Southeast => frame % self.southeast.len(),
-        };
+#[cfg(test)]
+mod tests {
+    use image::{ImageFormat, Rgba, RgbaImage};
 
-        match sprite_state {
-            State::Idle => self.idle.get(frame_idx).unwrap(),
-            State::Sleep => self.sleep.get(frame_idx).unwrap(),
-            State::East => self.east.get(frame_idx).unwrap(),
-            State::Northeast => self.northeast.get(frame_idx).unwrap(),
-            State::North => self.north.get(frame_idx).unwrap(),
-            State::Northwest => self.northwest.get(frame_idx).unwrap(),
-            State::West => self.west.get(frame_idx).unwrap(),
-            State::Southwest => self.southwest.get(frame_idx).unwrap(),
-            State::South => self.south.get(frame_idx).unwrap(),
-            State::Southeast => self.southeast.get(frame_idx).unwrap(),
+    use super::*;
+
+    fn left_pixel_animation() -> Animation {
+        let mut image = RgbaImage::new(2, 1);
+        image.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        let sprite = Sprite::from_image(image.into(), ImageFormat::Png);
+
+        let mut animation = Animation::new();
+        animation.push(sprite, Duration::from_secs(1));
+        animation
+    }
+
+    fn left_pixel(sprite: &Sprite) -> [u8; 4] {
+        sprite.as_image().to_rgba8().get_pixel(0, 0).0
+    }
+
+    #[test]
+    fn test_derive_missing() {
+        let mut sheet = Sheet::new();
+        sheet.set(&State::Idle, left_pixel_animation());
+        sheet.set(&State::East, left_pixel_animation());
+        sheet
+            .derive_missing()
+            .expect("Idle is enough to derive everything");
+
+        for sprite_state in State::ALL.iter() {
+            assert_eq!(sheet.animation(sprite_state).len(), 1);
         }
+
+        let red = [255, 0, 0, 255];
+        assert_eq!(left_pixel(sheet.get_sprite(&State::East, 0)), red);
+        assert_ne!(left_pixel(sheet.get_sprite(&State::West, 0)), red);
+        assert_eq!(left_pixel(sheet.get_sprite(&State::Northeast, 0)), red);
+        assert_ne!(left_pixel(sheet.get_sprite(&State::Southwest, 0)), red);
+    }
+
+    #[test]
+    fn test_get_transition_sprite_at() {
+        let mut sheet = Sheet::new();
+        sheet.set(&State::Idle, left_pixel_animation());
+        sheet.set(&State::Sleep, left_pixel_animation().mirrored());
+        sheet.set_transition(&State::Sleep, &State::Idle, left_pixel_animation());
+        sheet.derive_missing().unwrap();
+
+        let red = [255, 0, 0, 255];
+        let left_pixel_at = |sheet: &Sheet, millis| {
+            let elapsed = Duration::from_millis(millis);
+            left_pixel(sheet.get_transition_sprite_at(&State::Idle, &State::Sleep, elapsed))
+        };
+
+        // no transition into sleep, so it plays sleep straight away
+        assert_ne!(left_pixel_at(&sheet, 0), red);
+
+        sheet.set_transition(&State::Idle, &State::Sleep, left_pixel_animation());
+        assert_eq!(left_pixel_at(&sheet, 0), red);
+        assert_eq!(left_pixel_at(&sheet, 999), red);
+        assert_ne!(left_pixel_at(&sheet, 1000), red);
+    }
+
+    #[test]
+    fn test_derive_missing_requires_idle() {
+        let mut sheet = Sheet::new();
+        sheet.set(&State::East, left_pixel_animation());
+        assert!(sheet.derive_missing().is_err());
     }
 }
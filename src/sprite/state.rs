@@ -1,8 +1,11 @@
 use std::{f64::consts::PI, fmt, ops::Range};
 
+use serde::Serialize;
+
 use crate::math::Vec2;
 
-#[derive(Debug, Clone, PartialEq, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum State {
     Idle,
     Sleep,
@@ -14,6 +17,9 @@ pub enum State {
     Southwest,
     South,
     Southeast,
+    Sniff,
+    Nap,
+    Wave,
 }
 
 #[rustfmt::skip]
@@ -42,11 +48,22 @@ impl TryFrom<&str> for State {
             "southwest" => Ok(Self::Southwest),
             "south" => Ok(Self::South),
             "southeast" => Ok(Self::Southeast),
+            "sniff" => Ok(Self::Sniff),
+            "nap" => Ok(Self::Nap),
+            "wave" => Ok(Self::Wave),
             _ => Err(()),
         }
     }
 }
 
+/**
+ * Parses a transition name like `sleep->idle` into its from and to states.
+ */
+pub(crate) fn parse_transition(value: &str) -> Result<(State, State), ()> {
+    let (from, to) = value.split_once("->").ok_or(())?;
+    Ok((State::try_from(from.trim())?, State::try_from(to.trim())?))
+}
+
 impl fmt::Display for State {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", format!("{:?}", self).to_lowercase())
@@ -54,6 +71,48 @@ impl fmt::Display for State {
 }
 
 impl State {
+    pub const ALL: [State; 13] = [
+        State::Idle,
+        State::Sleep,
+        State::East,
+        State::Northeast,
+        State::North,
+        State::Northwest,
+        State::West,
+        State::Southwest,
+        State::South,
+        State::Southeast,
+        State::Sniff,
+        State::Nap,
+        State::Wave,
+    ];
+
+    /**
+     * States to borrow frames from when this state has none, in order of preference.
+     * The flag is whether the borrowed frames are mirrored horizontally.
+     * Diagonals prefer their mirror image, then the nearest horizontal cardinal,
+     * then the nearest vertical one. Interactions look like idling or sleeping.
+     * Everything ends up at idle.
+     */
+    #[rustfmt::skip]
+    pub(crate) fn fallbacks(&self) -> &'static [(State, bool)] {
+        match self {
+            State::Idle =>      &[],
+            State::Sleep =>     &[(State::Idle, false)],
+            State::East =>      &[(State::West, true),      (State::Idle, false)],
+            State::West =>      &[(State::East, true),      (State::Idle, false)],
+            State::North =>     &[(State::Idle, false)],
+            State::South =>     &[(State::Idle, false)],
+            State::Northeast => &[(State::Northwest, true), (State::East, false), (State::West, true), (State::North, false), (State::Idle, false)],
+            State::Northwest => &[(State::Northeast, true), (State::West, false), (State::East, true), (State::North, false), (State::Idle, false)],
+            State::Southeast => &[(State::Southwest, true), (State::East, false), (State::West, true), (State::South, false), (State::Idle, false)],
+            State::Southwest => &[(State::Southeast, true), (State::West, false), (State::East, true), (State::South, false), (State::Idle, false)],
+            State::Sniff =>     &[(State::Idle, false)],
+            State::Nap =>       &[(State::Sleep, false),     (State::Idle, false)],
+            State::Wave =>      &[(State::Idle, false)],
+        }
+    }
+
     pub fn from_delta(delta: &Vec2) -> Option<Self> {
         if delta.x == 0.0 && delta.y == 0.0 {
             return None;
@@ -1,7 +1,7 @@
 use std::{fs::File, io::Write, path::PathBuf};
 
 use image::ImageFormat;
-use kennel_club::Kennel;
+use kennel_club::{DEFAULT_ROOM, ImageOptions, Kennel, KennelClub, Layer, Theme, theme::Border};
 use rand::{SeedableRng, rngs::SmallRng};
 
 static RNG_SEED: u64 = 1;
@@ -13,7 +13,7 @@ fn test_kennel() {
     let mut kennel = Kennel::load(&dir, &mut rng).expect("Error during kennel initialization");
 
     for _ in 0..1000000 {
-        kennel = kennel
+        (kennel, _) = kennel
             .next(&mut rng)
             .expect("Error during kennel iteration");
     }
@@ -32,3 +32,91 @@ fn test_image() {
     file.write_all(&image_data)
         .expect("Error during image writing");
 }
+
+#[test]
+fn test_tiny_image() {
+    let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+    let dir = PathBuf::from("./data");
+    let kennel = Kennel::load(&dir, &mut rng).expect("Error during kennel initialization");
+
+    for (width, height) in [(1, 1), (3, 5), (16, 4)] {
+        kennel
+            .get_image(width, height, ImageFormat::Png)
+            .expect("Error during image processing");
+    }
+}
+
+#[test]
+fn test_layered_image() {
+    let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+    let dir = PathBuf::from("./data");
+    let kennel = Kennel::load(&dir, &mut rng)
+        .expect("Error during kennel initialization")
+        .with_theme(Theme {
+            border: Some(Border { width: 0.02 }),
+            ..Theme::default()
+        });
+
+    let options = ImageOptions {
+        layers: vec![
+            Layer::Foreground,
+            Layer::Creatures,
+            Layer::Shadows,
+            Layer::Background,
+        ],
+    };
+    kennel
+        .get_image_with(256, 256, ImageFormat::Png, &options)
+        .expect("Error during image processing");
+}
+
+#[test]
+fn test_image_map() {
+    let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+    let dir = PathBuf::from("./data");
+    let kennel = Kennel::load(&dir, &mut rng).expect("Error during kennel initialization");
+
+    let options = ImageOptions {
+        layers: vec![Layer::Creatures, Layer::Labels],
+    };
+    kennel
+        .get_image_with(512, 256, ImageFormat::Png, &options)
+        .expect("Error during image processing");
+
+    let regions = kennel.get_hit_regions(512, 256);
+    assert_eq!(regions.len(), kennel.creatures().len());
+    serde_json::to_string(&regions).expect("Hit regions must serialize");
+
+    let map = kennel.get_image_map(512, 256, "kennel");
+    assert_eq!(map.matches("<area").count(), regions.len());
+}
+
+#[test]
+fn test_svg() {
+    let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+    let dir = PathBuf::from("./data");
+    let kennel = Kennel::load(&dir, &mut rng).expect("Error during kennel initialization");
+
+    let svg = kennel.get_svg().expect("Error rendering svg");
+    assert_eq!(svg.matches("<a href=").count(), kennel.creatures().len());
+}
+
+#[test]
+fn test_club() {
+    let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+    let dir = PathBuf::from("./data");
+    let kennel = Kennel::load(&dir, &mut rng).expect("Error during kennel initialization");
+    let mut club = KennelClub::load(&dir, &mut rng).expect("Error during club initialization");
+
+    // without rooms, everyone is in the one room
+    let rooms: Vec<&str> = club.rooms().map(|(room, _)| room).collect();
+    assert_eq!(rooms, vec![DEFAULT_ROOM]);
+    let room = club
+        .room(DEFAULT_ROOM)
+        .expect("The default room must exist");
+    assert_eq!(room.creatures().len(), kennel.creatures().len());
+
+    for _ in 0..100 {
+        club.tick(&mut rng).expect("Error during club iteration");
+    }
+}